@@ -1,16 +1,30 @@
 use futures::StreamExt;
 use reqwest::Client as HttpClient;
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, de::DeserializeOwned};
 use serde_json::json;
 use std::time::Duration;
 
 use crate::{
     aliyun::{
-        AliYunModelType, AliyunAIError, get_model,
+        AliYunModelType, AliyunAIError, get_available_model_types, get_model,
         model::{ApiFormat, Model},
         types::AliyunStreamCallback,
-    }, 
-    types::{ChatMessage, ChatRole, OHLCV},
+    },
+    analysis::{Analysis, AnalysisContext, AnalysisType},
+    capability::Capability,
+    chat::{self, ChatClient, SharedOptions},
+    encoding::PriceEncoding,
+    gaps::FillStrategy,
+    health::HealthReport,
+    indicators::IndicatorContext,
+    language::{Language, apply_language},
+    prompts::PromptTemplate,
+    serialization::OhlcvFormat,
+    types::{
+        Candle, ChatMessage, ErrorKind, ModelId, OHLCV, PredictionFailure, PredictionReport,
+        ResponseFormat,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -39,6 +53,28 @@ pub struct ChatOptions {
     pub stream: bool,
     pub system_prompt: Option<String>,
     pub model_type: Option<AliYunModelType>,
+    /// Price representation used by `predict_ohlcv` prompts.
+    pub encoding: PriceEncoding,
+    /// Layout of the OHLCV rows in `predict_ohlcv` prompts and replies.
+    pub data_format: OhlcvFormat,
+    /// Indicators and summary stats appended to `predict_ohlcv` prompts.
+    pub indicators: Option<IndicatorContext>,
+    /// Patterns and levels computed locally for `analyze_ohlcv` prompts.
+    pub analysis_context: AnalysisContext,
+    /// Response language; localizes analysis prompts and appends a response
+    /// instruction to the system prompt. `None` leaves messages untouched.
+    pub language: Option<Language>,
+    /// Overrides the registered `predict_ohlcv` prompt template for this call.
+    pub prompt_template: Option<PromptTemplate>,
+    /// Constrains the reply format. `predict_ohlcv` picks the strictest format the
+    /// model supports when this is `None`; `Some(ResponseFormat::Text)` opts out.
+    pub response_format: Option<ResponseFormat>,
+    /// Times `chat_extract` and `predict_ohlcv` re-ask with the error after an
+    /// unusable reply. Each retry is a billed request; `0` disables them.
+    pub max_retries: u32,
+    /// How `predict_candles` treats missing bars. With a strategy, the gaps are
+    /// filled before prompting and listed in the prompt; `None` sends the series as is.
+    pub gap_fill: Option<FillStrategy>,
 }
 
 impl Default for ChatOptions {
@@ -49,6 +85,15 @@ impl Default for ChatOptions {
             stream: false,
             system_prompt: None,
             model_type: None,
+            encoding: PriceEncoding::Raw,
+            data_format: OhlcvFormat::Json,
+            indicators: None,
+            analysis_context: AnalysisContext::default(),
+            language: None,
+            prompt_template: None,
+            response_format: None,
+            max_retries: 2,
+            gap_fill: None,
         }
     }
 }
@@ -140,20 +185,7 @@ impl AliyunAI {
         message: &str,
         options: Option<ChatOptions>,
     ) -> Result<String, AliyunAIError> {
-        let opts = options.unwrap_or_default();
-        let mut messages = Vec::new();
-        if let Some(system_prompt) = &opts.system_prompt {
-            messages.push(ChatMessage {
-                role: ChatRole::System,
-                content: system_prompt.clone(),
-            });
-        }
-        messages.push(ChatMessage {
-            role: ChatRole::User,
-            content: message.to_string(),
-        });
-        let response = self.chat_completion(&messages, Some(opts)).await?;
-        self.extract_content(&response)
+        chat::chat(self, message, options).await
     }
 
     pub async fn chat_completion(
@@ -162,16 +194,28 @@ impl AliyunAI {
         options: Option<ChatOptions>,
     ) -> Result<serde_json::Value, AliyunAIError> {
         let opts = options.unwrap_or_default();
+        let messages = match &opts.language {
+            Some(language) => {
+                &apply_language(messages, language).map_err(AliyunAIError::ConfigError)?
+            }
+            None => messages,
+        };
         let model_type = opts.model_type.unwrap_or(self.config.model_type);
         let model = get_model(model_type)
             .ok_or_else(|| AliyunAIError::ModelNotSupported(format!("{:?}", model_type)))?;
+        Self::check_response_format(&model, opts.response_format.as_ref())?;
         let temperature = opts.temperature.unwrap_or(0.7);
         let max_tokens = opts.max_tokens.unwrap_or(1000);
         let stream = opts.stream;
         let request_data = match model.format {
-            ApiFormat::OpenAI => {
-                self.build_openai_request(&model.name, messages, temperature, max_tokens, stream)
-            }
+            ApiFormat::OpenAI => self.build_openai_request(
+                &model.name,
+                messages,
+                temperature,
+                max_tokens,
+                stream,
+                opts.response_format.as_ref(),
+            ),
             ApiFormat::DashScope => {
                 self.build_dashscope_request(&model.name, messages, temperature, max_tokens)
             }
@@ -189,6 +233,12 @@ impl AliyunAI {
         options: Option<ChatOptions>,
     ) -> Result<(), AliyunAIError> {
         let opts = options.unwrap_or_default();
+        let messages = match &opts.language {
+            Some(language) => {
+                &apply_language(messages, language).map_err(AliyunAIError::ConfigError)?
+            }
+            None => messages,
+        };
         let model_type = opts.model_type.unwrap_or(self.config.model_type);
         let model = get_model(model_type)
             .ok_or_else(|| AliyunAIError::ModelNotSupported(format!("{:?}", model_type)))?;
@@ -197,13 +247,32 @@ impl AliyunAI {
         }
         let temperature = opts.temperature.unwrap_or(0.7);
         let max_tokens = opts.max_tokens.unwrap_or(1000);
-        let request_data =
-            self.build_openai_request(&model.name, messages, temperature, max_tokens, true);
+        Self::check_response_format(&model, opts.response_format.as_ref())?;
+        let request_data = self.build_openai_request(
+            &model.name,
+            messages,
+            temperature,
+            max_tokens,
+            true,
+            opts.response_format.as_ref(),
+        );
         self.make_stream_request(&model.endpoint, &request_data, &mut callback)
             .await?;
         Ok(())
     }
 
+    /// Asks for a reply matching `T`'s JSON schema and deserializes it. The schema is
+    /// added to the system prompt and, when the model supports it, sent as the
+    /// `response_format`. Unusable replies are retried up to `max_retries` times
+    /// (twice by default) with the error appended to the conversation.
+    pub async fn chat_extract<T: DeserializeOwned + JsonSchema>(
+        &self,
+        messages: &[ChatMessage],
+        options: Option<ChatOptions>,
+    ) -> Result<T, AliyunAIError> {
+        chat::chat_extract(self, messages, options).await
+    }
+
     pub fn set_model(&mut self, model_type: AliYunModelType) -> Result<(), AliyunAIError> {
         let model = get_model(model_type)
             .ok_or_else(|| AliyunAIError::ModelNotSupported(format!("{:?}", model_type)))?;
@@ -238,6 +307,19 @@ impl AliyunAI {
         }
     }
 
+    /// Probes a model (the configured one by default) for auth, latency, streaming and JSON replies.
+    pub async fn health_check(&self, model_type: Option<AliYunModelType>) -> HealthReport {
+        chat::health_check(self, model_type.unwrap_or(self.config.model_type)).await
+    }
+
+    /// Probes every available chat model, a few at a time.
+    pub async fn health_check_all(&self) -> Vec<HealthReport> {
+        chat::health_check_all(self).await
+    }
+
+    /// Predicts the next `count` bars (1 by default, at most 50). A reply that fails
+    /// validation is re-prompted with the problems found, up to `max_retries` times
+    /// (twice by default); set `max_retries: 0` to accept only the first reply.
     pub async fn predict_ohlcv(
         &self,
         ohlcv_array: &[OHLCV],
@@ -245,97 +327,73 @@ impl AliyunAI {
         count: Option<usize>,
         options: Option<ChatOptions>,
     ) -> Result<Vec<OHLCV>, AliyunAIError> {
-        let processed_instructions =
-            instructions.unwrap_or("Based on these OHLCV data, predict the next period");
-        let processed_count = count.unwrap_or(1);
-        if processed_count == 0 {
-            return Err(AliyunAIError::ConfigError(
-                "Count must be positive integer".to_string(),
-            ));
-        }
-        const MAX_COUNT: usize = 50;
-        if processed_count > MAX_COUNT {
-            return Err(AliyunAIError::ConfigError(format!(
-                "Count parameter too large: {}. Maximum allowed is {}",
-                processed_count, MAX_COUNT
-            )));
-        }
-        let count_message = if processed_count == 1 {
-            "Return EXACTLY 1 OHLCV object for the next period.".to_string()
-        } else {
-            format!(
-                "Return EXACTLY {} consecutive OHLCV objects for the next {} periods.",
-                processed_count, processed_count
-            )
-        };
-        let system_prompt = format!(
-            r#"You are a professional financial data analysis AI. The user will give you an array of OHLCV (Open, High, Low, Close, Volume) data.
-Your task: {}
-CRITICAL RULES:
-1. {}
-2. Return ONLY a JSON array of OHLCV objects, NO explanations, comments, or other text
-3. The OHLCV array format must match: [{{open, high, low, close, volume}}, ...]
-4. All numbers must be valid numbers
-5. Ensure technical rationality (high >= low, high >= close >= low, volume >= 0)
-6. Maintain consistency with historical trends and patterns
-7. For technical analysis, provide reasonable values based on typical patterns
-8. Do not include markdown formatting, only pure JSON
-{}"#,
-            processed_instructions,
-            count_message,
-            if processed_count == 1 {
-                r#"Example of valid response for 1 period:
-[{"open": 115.5, "high": 118.0, "low": 114.0, "close": 117.0, "volume": 1350000}]"#
-            } else {
-                &format!(
-                    r#"Example of valid response for {} periods:
-[
-  {{"open": 115.5, "high": 118.0, "low": 114.0, "close": 117.0, "volume": 1350000}},
-  {{"open": 117.5, "high": 120.0, "low": 116.0, "close": 119.0, "volume": 1400000}}
-  {} more OHLCV objects following the same pattern
-]"#,
-                    processed_count,
-                    if processed_count > 2 {
-                        format!("{}", processed_count - 2)
-                    } else {
-                        String::new()
-                    }
-                )
-            }
-        );
-        let data_string = serde_json::to_string_pretty(ohlcv_array).map_err(|e| {
-            AliyunAIError::ParseError(format!("Failed to serialize OHLCV data: {}", e))
-        })?;
-        let user_message = format!(
-            "Here is the historical OHLCV data ({} periods):\n{}\nPlease process this data according to the system instructions. Remember to return EXACTLY {} OHLCV object(s) in a JSON array with no additional text.",
-            ohlcv_array.len(),
-            data_string,
-            processed_count
-        );
-        let messages = vec![
-            ChatMessage {
-                role: ChatRole::System,
-                content: system_prompt,
-            },
-            ChatMessage {
-                role: ChatRole::User,
-                content: user_message,
-            },
-        ];
-        let estimated_tokens = (processed_count * 50 + 100) as u32;
-        let max_tokens = options
-            .as_ref()
-            .and_then(|o| o.max_tokens)
-            .unwrap_or(1000)
-            .max(estimated_tokens);
-        let mut chat_options = options.unwrap_or_default();
-        chat_options.max_tokens = Some(max_tokens);
-        chat_options.temperature = Some(chat_options.temperature.unwrap_or(0.3));
-        chat_options.system_prompt = None;
-        let response = self.chat_completion(&messages, Some(chat_options)).await?;
-        let content = self.extract_content(&response)?;
-        let result = self.parse_ohlcv_response(&content, processed_count)?;
-        Ok(result)
+        Ok(self
+            .predict_ohlcv_with_report(ohlcv_array, instructions, count, options)
+            .await
+            .map_err(|failure| failure.error)?
+            .predictions)
+    }
+
+    /// `predict_ohlcv` that also reports the attempts and tokens used. A reply that
+    /// fails validation is sent back with the error, up to `max_retries` times. On
+    /// failure the report of the attempts made comes with the error.
+    pub async fn predict_ohlcv_with_report(
+        &self,
+        ohlcv_array: &[OHLCV],
+        instructions: Option<&str>,
+        count: Option<usize>,
+        options: Option<ChatOptions>,
+    ) -> Result<PredictionReport, PredictionFailure<AliyunAIError>> {
+        chat::predict_ohlcv_with_report(self, ohlcv_array, instructions, count, options).await
+    }
+
+    /// Analyzes OHLCV data and returns the model's text analysis.
+    /// Summary statistics, candlestick patterns and support/resistance levels are
+    /// computed locally and embedded in the prompt.
+    pub async fn analyze_ohlcv(
+        &self,
+        ohlcv_array: &[OHLCV],
+        analysis_type: Option<AnalysisType>,
+        message: Option<&str>,
+        options: Option<ChatOptions>,
+    ) -> Result<String, AliyunAIError> {
+        chat::analyze_ohlcv(self, ohlcv_array, analysis_type, message, options).await
+    }
+
+    /// Like `analyze_ohlcv`, but asks for JSON and validates it into an `Analysis`. The
+    /// schema or JSON mode is requested when the model supports it. Replies that deviate
+    /// from the expected shape are sent back with every problem found, up to
+    /// `max_retries` times, before failing with a `ParseError`.
+    pub async fn analyze_ohlcv_structured(
+        &self,
+        ohlcv_array: &[OHLCV],
+        analysis_type: Option<AnalysisType>,
+        message: Option<&str>,
+        options: Option<ChatOptions>,
+    ) -> Result<Analysis, AliyunAIError> {
+        chat::analyze_ohlcv_structured(self, ohlcv_array, analysis_type, message, options).await
+    }
+
+    /// Predicts the next candles of a timestamped series.
+    /// Predicted candles continue the input timestamps at the series interval. Set
+    /// `gap_fill` to fill missing bars and point them out in the prompt.
+    pub async fn predict_candles(
+        &self,
+        candles: &[Candle],
+        instructions: Option<&str>,
+        count: Option<usize>,
+        options: Option<ChatOptions>,
+    ) -> Result<Vec<Candle>, AliyunAIError> {
+        chat::predict_candles(self, candles, instructions, count, options).await
+    }
+
+    /// Rejects a `response_format` the model cannot honour instead of letting the API ignore it.
+    fn check_response_format(
+        model: &Model,
+        response_format: Option<&ResponseFormat>,
+    ) -> Result<(), AliyunAIError> {
+        chat::check_response_format(&model.name, &model.typed_capabilities(), response_format)
+            .map_err(AliyunAIError::ConfigError)
     }
 
     fn build_openai_request(
@@ -345,14 +403,19 @@ CRITICAL RULES:
         temperature: f32,
         max_tokens: u32,
         stream: bool,
+        response_format: Option<&ResponseFormat>,
     ) -> serde_json::Value {
-        json!({
+        let mut request = json!({
             "model": model,
             "messages": messages,
             "temperature": temperature,
             "max_tokens": max_tokens,
             "stream": stream,
-        })
+        });
+        if let Some(response_format) = response_format {
+            request["response_format"] = response_format.to_request_value();
+        }
+        request
     }
 
     fn build_dashscope_request(
@@ -392,6 +455,16 @@ CRITICAL RULES:
             .await
             .map_err(|e| AliyunAIError::NetworkError(format!("HTTP request failed: {}", e)))?;
         let status = response.status();
+        if status == 429 {
+            return Err(AliyunAIError::RateLimitError(
+                "Rate limit exceeded".to_string(),
+            ));
+        }
+        if status == 401 {
+            return Err(AliyunAIError::AuthError(
+                "Authentication failed. Please check your API key.".to_string(),
+            ));
+        }
         if !status.is_success() {
             let error_text = response
                 .text()
@@ -432,6 +505,16 @@ CRITICAL RULES:
             .await
             .map_err(|e| AliyunAIError::NetworkError(format!("HTTP request failed: {}", e)))?;
         let status = response.status();
+        if status == 429 {
+            return Err(AliyunAIError::RateLimitError(
+                "Rate limit exceeded".to_string(),
+            ));
+        }
+        if status == 401 {
+            return Err(AliyunAIError::AuthError(
+                "Authentication failed. Please check your API key.".to_string(),
+            ));
+        }
         if !status.is_success() {
             let response_clone = response;
             let error_text = response_clone
@@ -458,20 +541,15 @@ CRITICAL RULES:
                 buffer.clear();
             }
             for line in lines.iter().take(lines.len().saturating_sub(1)) {
-                if line.starts_with("data: ") {
-                    let data = &line[6..];
+                if let Some(data) = line.strip_prefix("data: ") {
                     if data == "[DONE]" {
                         callback("".to_string(), true);
                         return Ok(());
                     }
-                    match serde_json::from_str::<serde_json::Value>(data) {
-                        Ok(parsed) => {
-                            if let Some(content) = parsed["choices"][0]["delta"]["content"].as_str()
-                            {
-                                callback(content.to_string(), false);
-                            }
-                        }
-                        Err(_) => {}
+                    if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(data)
+                        && let Some(content) = parsed["choices"][0]["delta"]["content"].as_str()
+                    {
+                        callback(content.to_string(), false);
                     }
                 }
             }
@@ -495,75 +573,91 @@ CRITICAL RULES:
             ))
         }
     }
+}
+
+impl ChatClient for AliyunAI {
+    type Model = AliYunModelType;
+    type Options = ChatOptions;
+    type Error = AliyunAIError;
 
-    fn parse_ohlcv_response(
+    async fn complete(
         &self,
-        content: &str,
-        expected_count: usize,
-    ) -> Result<Vec<OHLCV>, AliyunAIError> {
-        let json_match = regex::Regex::new(r"\[[\s\S]*\]")
-            .unwrap()
-            .find(content)
-            .map(|m| m.as_str());
-        let json_str = json_match.unwrap_or(content);
-        let parsed: Vec<serde_json::Value> = serde_json::from_str(json_str)
-            .map_err(|e| AliyunAIError::ParseError(format!("Failed to parse JSON: {}", e)))?;
-        let mut result = Vec::new();
-        for (i, item) in parsed.iter().enumerate() {
-            let obj = item.as_object().ok_or_else(|| {
-                AliyunAIError::ParseError(format!("Element {} is not an object", i))
-            })?;
-            let open = obj.get("open").and_then(|v| v.as_f64()).ok_or_else(|| {
-                AliyunAIError::ParseError(format!("Element {} missing or invalid 'open' field", i))
-            })?;
-            let high = obj.get("high").and_then(|v| v.as_f64()).ok_or_else(|| {
-                AliyunAIError::ParseError(format!("Element {} missing or invalid 'high' field", i))
-            })?;
-            let low = obj.get("low").and_then(|v| v.as_f64()).ok_or_else(|| {
-                AliyunAIError::ParseError(format!("Element {} missing or invalid 'low' field", i))
-            })?;
-            let close = obj.get("close").and_then(|v| v.as_f64()).ok_or_else(|| {
-                AliyunAIError::ParseError(format!("Element {} missing or invalid 'close' field", i))
-            })?;
-            let volume = obj.get("volume").and_then(|v| v.as_f64()).ok_or_else(|| {
-                AliyunAIError::ParseError(format!(
-                    "Element {} missing or invalid 'volume' field",
-                    i
-                ))
-            })?;
-            if high < low {
-                return Err(AliyunAIError::ParseError(format!(
-                    "Element {}: high cannot be lower than low",
-                    i
-                )));
-            }
-            if close < low || close > high {
-                return Err(AliyunAIError::ParseError(format!(
-                    "Element {}: close must be between low and high",
-                    i
-                )));
-            }
-            if volume < 0.0 {
-                return Err(AliyunAIError::ParseError(format!(
-                    "Element {}: volume must be non-negative",
-                    i
-                )));
-            }
-            result.push(OHLCV {
-                open,
-                high,
-                low,
-                close,
-                volume,
-            });
-        }
-        if result.len() != expected_count {
-            return Err(AliyunAIError::ParseError(format!(
-                "AI returned {} OHLCV objects, but expected {}",
-                result.len(),
-                expected_count
-            )));
+        messages: &[ChatMessage],
+        options: Option<ChatOptions>,
+    ) -> Result<serde_json::Value, AliyunAIError> {
+        self.chat_completion(messages, options).await
+    }
+
+    async fn stream(
+        &self,
+        messages: &[ChatMessage],
+        callback: AliyunStreamCallback,
+        options: Option<ChatOptions>,
+    ) -> Result<(), AliyunAIError> {
+        self.chat_stream(messages, callback, options).await
+    }
+
+    fn content(&self, response: &serde_json::Value) -> Result<String, AliyunAIError> {
+        self.extract_content(response)
+    }
+
+    fn default_model(&self) -> AliYunModelType {
+        self.config.model_type
+    }
+
+    fn available_models() -> Vec<AliYunModelType> {
+        get_available_model_types()
+    }
+
+    fn model_id(model: AliYunModelType) -> ModelId {
+        ModelId::Aliyun(model)
+    }
+
+    fn capabilities(model: AliYunModelType) -> Vec<Capability> {
+        get_model(model)
+            .map(|model| model.typed_capabilities())
+            .unwrap_or_default()
+    }
+
+    fn options_model(options: &ChatOptions) -> Option<AliYunModelType> {
+        options.model_type
+    }
+
+    fn set_options_model(options: &mut ChatOptions, model: AliYunModelType) {
+        options.model_type = Some(model);
+    }
+
+    fn shared(options: &mut ChatOptions) -> SharedOptions<'_> {
+        SharedOptions {
+            temperature: &mut options.temperature,
+            max_tokens: &mut options.max_tokens,
+            stream: &mut options.stream,
+            system_prompt: &mut options.system_prompt,
+            encoding: &mut options.encoding,
+            data_format: &mut options.data_format,
+            indicators: &mut options.indicators,
+            analysis_context: &mut options.analysis_context,
+            language: &mut options.language,
+            prompt_template: &mut options.prompt_template,
+            response_format: &mut options.response_format,
+            max_retries: &mut options.max_retries,
+            gap_fill: &mut options.gap_fill,
         }
-        Ok(result)
+    }
+
+    fn parse_error(message: String) -> AliyunAIError {
+        AliyunAIError::ParseError(message)
+    }
+
+    fn config_error(message: String) -> AliyunAIError {
+        AliyunAIError::ConfigError(message)
+    }
+
+    fn error_kind(error: &AliyunAIError) -> ErrorKind {
+        error.kind()
+    }
+
+    fn is_bad_request(error: &AliyunAIError) -> bool {
+        matches!(error, AliyunAIError::ApiError(message) if message.starts_with("HTTP 400"))
     }
 }
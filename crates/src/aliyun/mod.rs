@@ -1,8 +1,8 @@
 //! # Example 1
 //!
-//! ```rust
-//! use aliyun_ai::AliyunAI;
-//! use aliyun_ai::model::AliYunModelType;
+//! ```rust,no_run
+//! use ohlcv_ai::aliyun::AliyunAI;
+//! use ohlcv_ai::aliyun::model::AliYunModelType;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -23,8 +23,8 @@
 //! # Example 2
 //!
 //! ```rust,no_run
-//! use aliyun_ai::{AliyunAI, create_aliyun_ai};
-//! use aliyun_ai::model::AliYunModelType;
+//! use ohlcv_ai::aliyun::{AliyunAI, create_aliyun_ai};
+//! use ohlcv_ai::aliyun::model::AliYunModelType;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -34,7 +34,7 @@
 //!     let response = ai.chat("hello!", None).await?;
 //!     println!("Response: {}", response);
 //!     
-//!     let config = aliyun_ai::AliyunConfig {
+//!     let config = ohlcv_ai::aliyun::AliyunConfig {
 //!         api_key: "your-api-key".to_string(),
 //!         model_type: AliYunModelType::QWEN_PLUS,
 //!         timeout: std::time::Duration::from_secs(60),
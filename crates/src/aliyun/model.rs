@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::capability::{Capability, LONG_CONTEXT_TOKENS, find_models};
+use crate::types::ModelId;
+
+#[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AliYunModelType {
     QWEN_TURBO,
@@ -78,44 +82,48 @@ impl AliYunModelType {
             Self::QWEN_OMNI_PRO => "qwen-omni-pro",
         }
     }
-    
-    pub fn from_str(s: &str) -> Option<Self> {
+}
+
+impl std::str::FromStr for AliYunModelType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "qwen-turbo" => Some(Self::QWEN_TURBO),
-            "qwen-plus" => Some(Self::QWEN_PLUS),
-            "qwen-max" => Some(Self::QWEN_MAX),
-            "qwen-max-longcontext" => Some(Self::QWEN_MAX_LONGCONTEXT),
-            "qwen2.5-0.5b" => Some(Self::QWEN_2_5B),
-            "qwen2.5-0.5b-instruct" => Some(Self::QWEN_2_5B_INSTRUCT),
-            "qwen2.5-7b" => Some(Self::QWEN_2_5B_7B),
-            "qwen2.5-7b-instruct" => Some(Self::QWEN_2_5B_7B_INSTRUCT),
-            "qwen2.5-14b" => Some(Self::QWEN_2_5B_14B),
-            "qwen2.5-14b-instruct" => Some(Self::QWEN_2_5B_14B_INSTRUCT),
-            "qwen2.5-32b" => Some(Self::QWEN_2_5B_32B),
-            "qwen2.5-32b-instruct" => Some(Self::QWEN_2_5B_32B_INSTRUCT),
-            "qwen2.5-72b" => Some(Self::QWEN_2_5B_72B),
-            "qwen2.5-72b-instruct" => Some(Self::QWEN_2_5B_72B_INSTRUCT),
-            "qwen2.5-coder" => Some(Self::QWEN_2_5B_CODER),
-            "qwen2.5-coder-7b" => Some(Self::QWEN_2_5B_CODER_7B),
-            "qwen2.5-coder-14b" => Some(Self::QWEN_2_5B_CODER_14B),
-            "qwen2.5-coder-32b" => Some(Self::QWEN_2_5B_CODER_32B),
-            "qwen-vl-lite" => Some(Self::QWEN_VL_LITE),
-            "qwen-vl-plus" => Some(Self::QWEN_VL_PLUS),
-            "qwen-vl-max" => Some(Self::QWEN_VL_MAX),
-            "qwen-audio-turbo" => Some(Self::QWEN_AUDIO_TURBO),
-            "qwen-audio-chat" => Some(Self::QWEN_AUDIO_CHAT),
-            "qwen-math-7b" => Some(Self::QWEN_MATH_7B),
-            "llama2-7b-chat-v2" => Some(Self::LLAMA2_7B_CHAT_V2),
-            "baichuan2-7b-chat-v1" => Some(Self::BAICHUAN2_7B_CHAT_V1),
-            "qwen-financial" => Some(Self::QWEN_FINANCIAL),
-            "qwen-financial-14b" => Some(Self::QWEN_FINANCIAL_14B),
-            "qwen-financial-32b" => Some(Self::QWEN_FINANCIAL_32B),
-            "qwen-medical" => Some(Self::QWEN_MEDICAL),
-            "qwen-medical-14b" => Some(Self::QWEN_MEDICAL_14B),
-            "qwen-medical-32b" => Some(Self::QWEN_MEDICAL_32B),
-            "qwen-omni" => Some(Self::QWEN_OMNI),
-            "qwen-omni-pro" => Some(Self::QWEN_OMNI_PRO),
-            _ => None,
+            "qwen-turbo" => Ok(Self::QWEN_TURBO),
+            "qwen-plus" => Ok(Self::QWEN_PLUS),
+            "qwen-max" => Ok(Self::QWEN_MAX),
+            "qwen-max-longcontext" => Ok(Self::QWEN_MAX_LONGCONTEXT),
+            "qwen2.5-0.5b" => Ok(Self::QWEN_2_5B),
+            "qwen2.5-0.5b-instruct" => Ok(Self::QWEN_2_5B_INSTRUCT),
+            "qwen2.5-7b" => Ok(Self::QWEN_2_5B_7B),
+            "qwen2.5-7b-instruct" => Ok(Self::QWEN_2_5B_7B_INSTRUCT),
+            "qwen2.5-14b" => Ok(Self::QWEN_2_5B_14B),
+            "qwen2.5-14b-instruct" => Ok(Self::QWEN_2_5B_14B_INSTRUCT),
+            "qwen2.5-32b" => Ok(Self::QWEN_2_5B_32B),
+            "qwen2.5-32b-instruct" => Ok(Self::QWEN_2_5B_32B_INSTRUCT),
+            "qwen2.5-72b" => Ok(Self::QWEN_2_5B_72B),
+            "qwen2.5-72b-instruct" => Ok(Self::QWEN_2_5B_72B_INSTRUCT),
+            "qwen2.5-coder" => Ok(Self::QWEN_2_5B_CODER),
+            "qwen2.5-coder-7b" => Ok(Self::QWEN_2_5B_CODER_7B),
+            "qwen2.5-coder-14b" => Ok(Self::QWEN_2_5B_CODER_14B),
+            "qwen2.5-coder-32b" => Ok(Self::QWEN_2_5B_CODER_32B),
+            "qwen-vl-lite" => Ok(Self::QWEN_VL_LITE),
+            "qwen-vl-plus" => Ok(Self::QWEN_VL_PLUS),
+            "qwen-vl-max" => Ok(Self::QWEN_VL_MAX),
+            "qwen-audio-turbo" => Ok(Self::QWEN_AUDIO_TURBO),
+            "qwen-audio-chat" => Ok(Self::QWEN_AUDIO_CHAT),
+            "qwen-math-7b" => Ok(Self::QWEN_MATH_7B),
+            "llama2-7b-chat-v2" => Ok(Self::LLAMA2_7B_CHAT_V2),
+            "baichuan2-7b-chat-v1" => Ok(Self::BAICHUAN2_7B_CHAT_V1),
+            "qwen-financial" => Ok(Self::QWEN_FINANCIAL),
+            "qwen-financial-14b" => Ok(Self::QWEN_FINANCIAL_14B),
+            "qwen-financial-32b" => Ok(Self::QWEN_FINANCIAL_32B),
+            "qwen-medical" => Ok(Self::QWEN_MEDICAL),
+            "qwen-medical-14b" => Ok(Self::QWEN_MEDICAL_14B),
+            "qwen-medical-32b" => Ok(Self::QWEN_MEDICAL_32B),
+            "qwen-omni" => Ok(Self::QWEN_OMNI),
+            "qwen-omni-pro" => Ok(Self::QWEN_OMNI_PRO),
+            _ => Err(format!("unknown Aliyun model '{}'", s)),
         }
     }
 }
@@ -139,6 +147,46 @@ pub struct Model {
     pub capabilities: Vec<String>,
 }
 
+/// Models DashScope documents `json_schema` structured output for; the others only
+/// accept `json_object`.
+const JSON_SCHEMA_MODELS: [&str; 3] = ["qwen-turbo", "qwen-plus", "qwen-max"];
+
+impl Model {
+    /// Typed capabilities derived from the descriptive tags and the API format.
+    pub fn typed_capabilities(&self) -> Vec<Capability> {
+        let mut capabilities: Vec<Capability> = Vec::new();
+        for capability in self
+            .capabilities
+            .iter()
+            .filter_map(|tag| Capability::from_tag(tag))
+        {
+            if !capabilities.contains(&capability) {
+                capabilities.push(capability);
+            }
+        }
+        // Only the OpenAI-compatible endpoint supports SSE streaming, tools and JSON output.
+        if self.format == ApiFormat::OpenAI {
+            capabilities.push(Capability::Streaming);
+            if capabilities.contains(&Capability::Chat) {
+                capabilities.push(Capability::FunctionCalling);
+                capabilities.push(Capability::JsonMode);
+                if JSON_SCHEMA_MODELS.contains(&self.name.as_str()) {
+                    capabilities.push(Capability::JsonSchema);
+                }
+            }
+        }
+        if self.context_length.unwrap_or(0) >= LONG_CONTEXT_TOKENS {
+            capabilities.push(Capability::LongContext);
+        }
+        capabilities.sort();
+        capabilities
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.typed_capabilities().contains(&capability)
+    }
+}
+
 pub fn get_aliyun_models() -> HashMap<AliYunModelType, Model> {
     let mut models = HashMap::new();
     
@@ -792,19 +840,14 @@ pub fn get_text_models() -> Vec<Model> {
 pub fn get_vision_models() -> Vec<Model> {
     get_all_models()
         .into_iter()
-        .filter(|model| 
-            model.capabilities.contains(&"image-understanding".to_string())
-        )
+        .filter(|model| model.supports(Capability::Vision))
         .collect()
 }
 
 pub fn get_audio_models() -> Vec<Model> {
     get_all_models()
         .into_iter()
-        .filter(|model| 
-            model.capabilities.contains(&"audio-processing".to_string()) ||
-            model.capabilities.contains(&"speech-recognition".to_string())
-        )
+        .filter(|model| model.supports(Capability::Audio))
         .collect()
 }
 
@@ -819,7 +862,7 @@ pub fn get_coding_models() -> Vec<Model> {
 }
 
 pub fn get_specialized_models() -> Vec<Model> {
-    let specialized_capabilities = vec![
+    let specialized_capabilities = [
         "financial-analysis".to_string(), 
         "medical-consultation".to_string(), 
         "mathematical-reasoning".to_string()
@@ -839,4 +882,15 @@ pub fn get_multimodal_models() -> Vec<Model> {
             model.capabilities.contains(&"multimodal".to_string())
         )
         .collect()
-}
\ No newline at end of file
+}
+
+/// Aliyun models supporting every capability; see [`find_models`].
+pub fn get_models_with_capabilities(capabilities: &[Capability]) -> Vec<Model> {
+    find_models(capabilities, None, None)
+        .into_iter()
+        .filter_map(|info| match info.id {
+            ModelId::Aliyun(model_type) => get_model(model_type),
+            _ => None,
+        })
+        .collect()
+}
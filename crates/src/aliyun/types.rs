@@ -1,3 +1,5 @@
+use crate::types::ErrorKind;
+
 pub type AliyunStreamCallback = Box<dyn FnMut(String, bool) + Send>;
 
 #[derive(Debug, thiserror::Error)]
@@ -16,4 +18,24 @@ pub enum AliyunAIError {
     ModelNotSupported(String),
     #[error("Streaming not supported")]
     StreamingNotSupported,
+    #[error("Rate limit exceeded: {0}")]
+    RateLimitError(String),
+    #[error("Authentication failed: {0}")]
+    AuthError(String),
+}
+
+impl AliyunAIError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ApiError(_) => ErrorKind::Api,
+            Self::NetworkError(_) => ErrorKind::Network,
+            Self::ParseError(_) => ErrorKind::Parse,
+            Self::ConfigError(_) => ErrorKind::Config,
+            Self::TimeoutError(_) => ErrorKind::Timeout,
+            Self::ModelNotSupported(_) => ErrorKind::ModelNotSupported,
+            Self::StreamingNotSupported => ErrorKind::StreamingNotSupported,
+            Self::RateLimitError(_) => ErrorKind::RateLimit,
+            Self::AuthError(_) => ErrorKind::Auth,
+        }
+    }
 }
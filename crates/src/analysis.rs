@@ -0,0 +1,493 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    capability::Capability,
+    extract::find_json_object,
+    language::{LanguagePack, fill},
+    levels::{LevelKind, LevelOptions, describe_levels, detect_levels},
+    patterns::{describe_patterns, detect_patterns},
+    serialization::OhlcvFormat,
+    summary::OhlcvSummary,
+    types::{ChatMessage, ChatRole, OHLCV, ResponseFormat},
+};
+
+/// Focus of `analyze_ohlcv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalysisType {
+    Trend,
+    Volume,
+    Technical,
+    #[default]
+    Comprehensive,
+}
+
+impl AnalysisType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Trend => "trend",
+            Self::Volume => "volume",
+            Self::Technical => "technical",
+            Self::Comprehensive => "comprehensive",
+        }
+    }
+
+    pub fn instruction(&self) -> &'static str {
+        match self {
+            Self::Trend => {
+                "Provide a detailed trend analysis of this OHLCV data, including price direction, support/resistance levels, and trend strength."
+            }
+            Self::Volume => {
+                "Analyze the volume patterns in this OHLCV data, including volume trends, unusual volume spikes, and volume-price relationships."
+            }
+            Self::Technical => {
+                "Perform technical analysis on this OHLCV data, identifying potential technical indicators, patterns, and trading signals."
+            }
+            Self::Comprehensive => {
+                "Provide a comprehensive analysis of this OHLCV data, covering trends, volume, technical aspects, and potential market implications."
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for AnalysisType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trend" => Ok(Self::Trend),
+            "volume" => Ok(Self::Volume),
+            "technical" => Ok(Self::Technical),
+            "comprehensive" => Ok(Self::Comprehensive),
+            _ => Err(format!("unknown analysis type '{}'", s)),
+        }
+    }
+}
+
+/// Locally computed facts added to `analyze_ohlcv` prompts.
+#[derive(Debug, Clone)]
+pub struct AnalysisContext {
+    /// Candlestick patterns ending within this many most recent bars; `None` to omit patterns.
+    pub recent_patterns: Option<usize>,
+    /// Support and resistance detection; `None` to omit levels.
+    pub levels: Option<LevelOptions>,
+}
+
+impl Default for AnalysisContext {
+    fn default() -> Self {
+        Self {
+            recent_patterns: Some(20),
+            levels: Some(LevelOptions::default()),
+        }
+    }
+}
+
+impl AnalysisContext {
+    /// Data characteristics section of the system prompt; empty for an empty series.
+    pub fn data_info(&self, ohlcv_array: &[OHLCV], pack: &LanguagePack) -> String {
+        let mut sections = vec![OhlcvSummary::from(ohlcv_array).describe(pack)];
+        if let Some(recent) = self.recent_patterns {
+            sections.push(describe_patterns(
+                &detect_patterns(ohlcv_array),
+                ohlcv_array.len(),
+                recent,
+                pack,
+            ));
+        }
+        if let Some(options) = &self.levels {
+            sections.push(describe_levels(&detect_levels(ohlcv_array, options), pack));
+        }
+        sections.retain(|section| !section.is_empty());
+        sections.join("\n")
+    }
+}
+
+/// Builds the system and user messages shared by the providers' `analyze_ohlcv`.
+pub(crate) fn analysis_messages(
+    ohlcv_array: &[OHLCV],
+    analysis_type: AnalysisType,
+    message: Option<&str>,
+    context: &AnalysisContext,
+    data_format: OhlcvFormat,
+    pack: &LanguagePack,
+) -> Result<Vec<ChatMessage>, String> {
+    let data_info = data_info_section(ohlcv_array, context, pack);
+    let system_prompt = fill(
+        &pack.analysis_system_prompt,
+        &[
+            ("instruction", pack.analysis_instruction(analysis_type)),
+            ("data_info", &data_info),
+        ],
+    );
+    let data_string = data_section(ohlcv_array, data_format, pack)?;
+    let count = ohlcv_array.len().to_string();
+    let user_message = match message {
+        Some(message) => fill(
+            &pack.analysis_question_prompt,
+            &[
+                ("count", &count),
+                ("data", &data_string),
+                ("message", message),
+            ],
+        ),
+        None => fill(
+            &pack.analysis_user_prompt,
+            &[("count", &count), ("data", &data_string)],
+        ),
+    };
+    Ok(vec![
+        ChatMessage {
+            role: ChatRole::System,
+            content: system_prompt,
+        },
+        ChatMessage {
+            role: ChatRole::User,
+            content: user_message,
+        },
+    ])
+}
+
+/// Localized "Data characteristics" block followed by a newline; empty when there is no data.
+fn data_info_section(
+    ohlcv_array: &[OHLCV],
+    context: &AnalysisContext,
+    pack: &LanguagePack,
+) -> String {
+    let data_info = context.data_info(ohlcv_array, pack);
+    if data_info.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n{}\n", pack.data_characteristics, data_info)
+    }
+}
+
+/// The serialized data, followed by the localized note on how to read its rows when needed.
+fn data_section(
+    ohlcv_array: &[OHLCV],
+    data_format: OhlcvFormat,
+    pack: &LanguagePack,
+) -> Result<String, String> {
+    let data = data_format
+        .serialize(ohlcv_array)
+        .map_err(|e| format!("Failed to serialize OHLCV data: {}", e))?;
+    let note = pack.format_note(data_format);
+    Ok(if note.is_empty() {
+        data
+    } else {
+        format!("{}\n{}", data, note)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendDirection {
+    Bullish,
+    Bearish,
+    Sideways,
+}
+
+impl TrendDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bullish => "bullish",
+            Self::Bearish => "bearish",
+            Self::Sideways => "sideways",
+        }
+    }
+}
+
+impl std::str::FromStr for TrendDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bullish" => Ok(Self::Bullish),
+            "bearish" => Ok(Self::Bearish),
+            "sideways" => Ok(Self::Sideways),
+            _ => Err(format!("unknown trend direction '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyLevel {
+    pub price: f64,
+    pub kind: LevelKind,
+    pub description: Option<String>,
+}
+
+/// Validated result of `analyze_ohlcv_structured`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Analysis {
+    pub summary: String,
+    pub details: Vec<String>,
+    pub recommendations: Vec<String>,
+    pub trend: TrendDirection,
+    /// Model's confidence in the analysis, in [0, 1].
+    pub confidence: f64,
+    pub key_levels: Vec<KeyLevel>,
+}
+
+pub const ANALYSIS_JSON_SHAPE: &str = r#"{"summary": "...", "details": ["...", "..."], "recommendations": ["...", "..."], "trend": "bullish" | "bearish" | "sideways", "confidence": 0.0-1.0, "key_levels": [{"price": 0.0, "kind": "support" | "resistance", "description": "..."}]}"#;
+
+/// JSON schema of `ANALYSIS_JSON_SHAPE`, sent as the `response_format` of models
+/// that support schemas.
+pub fn analysis_schema() -> Value {
+    let strings = json!({"type": "array", "items": {"type": "string"}});
+    json!({
+        "type": "object",
+        "properties": {
+            "summary": {"type": "string"},
+            "details": strings,
+            "recommendations": strings,
+            "trend": {"type": "string", "enum": ["bullish", "bearish", "sideways"]},
+            "confidence": {"type": "number", "minimum": 0, "maximum": 1},
+            "key_levels": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "price": {"type": "number"},
+                        "kind": {"type": "string", "enum": ["support", "resistance"]},
+                        "description": {"type": "string"},
+                    },
+                    "required": ["price", "kind"],
+                },
+            },
+        },
+        "required": ["summary", "details", "recommendations", "trend", "confidence", "key_levels"],
+    })
+}
+
+/// Strictest `response_format` a model with `capabilities` accepts for a structured
+/// analysis: the schema, plain JSON mode, or `None`.
+pub fn analysis_response_format(capabilities: &[Capability]) -> Option<ResponseFormat> {
+    if capabilities.contains(&Capability::JsonSchema) {
+        Some(ResponseFormat::JsonSchema {
+            name: "ohlcv_analysis".to_string(),
+            schema: analysis_schema(),
+            strict: false,
+        })
+    } else if capabilities.contains(&Capability::JsonMode) {
+        Some(ResponseFormat::JsonObject)
+    } else {
+        None
+    }
+}
+
+/// Builds the messages shared by the providers' `analyze_ohlcv_structured`.
+pub(crate) fn structured_analysis_messages(
+    ohlcv_array: &[OHLCV],
+    analysis_type: AnalysisType,
+    message: Option<&str>,
+    context: &AnalysisContext,
+    data_format: OhlcvFormat,
+    pack: &LanguagePack,
+) -> Result<Vec<ChatMessage>, String> {
+    let data_info = data_info_section(ohlcv_array, context, pack);
+    let system_prompt = fill(
+        &pack.structured_system_prompt,
+        &[
+            ("instruction", pack.analysis_instruction(analysis_type)),
+            ("data_info", &data_info),
+            ("shape", ANALYSIS_JSON_SHAPE),
+        ],
+    );
+    let data_string = data_section(ohlcv_array, data_format, pack)?;
+    let mut user_message = fill(
+        &pack.structured_user_prompt,
+        &[
+            ("count", &ohlcv_array.len().to_string()),
+            ("data", &data_string),
+        ],
+    );
+    if let Some(message) = message {
+        user_message.push_str(&fill(&pack.additional_request, &[("message", message)]));
+    }
+    Ok(vec![
+        ChatMessage {
+            role: ChatRole::System,
+            content: system_prompt,
+        },
+        ChatMessage {
+            role: ChatRole::User,
+            content: user_message,
+        },
+    ])
+}
+
+/// Parses a structured analysis reply, reporting every deviation from
+/// `ANALYSIS_JSON_SHAPE` rather than stopping at the first one.
+pub fn parse_analysis(content: &str) -> Result<Analysis, String> {
+    let value = find_json_object(content)?;
+    let mut problems = Vec::new();
+
+    let summary = match value.get("summary").and_then(|v| v.as_str()) {
+        Some(summary) if !summary.trim().is_empty() => summary.to_string(),
+        _ => {
+            problems.push("'summary' must be a non-empty string".to_string());
+            String::new()
+        }
+    };
+    let mut strings = |key: &str, required: bool| -> Vec<String> {
+        match value.get(key).and_then(|v| v.as_array()) {
+            Some(items) if items.iter().all(|item| item.is_string()) => {
+                if required && items.is_empty() {
+                    problems.push(format!("'{}' must not be empty", key));
+                }
+                items
+                    .iter()
+                    .filter_map(|item| item.as_str().map(str::to_string))
+                    .collect()
+            }
+            _ => {
+                problems.push(format!("'{}' must be an array of strings", key));
+                Vec::new()
+            }
+        }
+    };
+    let details = strings("details", true);
+    let recommendations = strings("recommendations", false);
+    let trend = value
+        .get("trend")
+        .and_then(|v| v.as_str())
+        .and_then(|trend| trend.parse().ok());
+    if trend.is_none() {
+        problems.push("'trend' must be one of \"bullish\", \"bearish\", \"sideways\"".to_string());
+    }
+    let confidence = value.get("confidence").and_then(|v| v.as_f64());
+    if !confidence.is_some_and(|c| (0.0..=1.0).contains(&c)) {
+        problems.push("'confidence' must be a number between 0 and 1".to_string());
+    }
+    let mut key_levels = Vec::new();
+    match value.get("key_levels").and_then(|v| v.as_array()) {
+        Some(items) => {
+            for (i, item) in items.iter().enumerate() {
+                let price = item
+                    .get("price")
+                    .and_then(|v| v.as_f64())
+                    .filter(|price| price.is_finite());
+                let kind = item
+                    .get("kind")
+                    .and_then(|v| v.as_str())
+                    .and_then(|kind| kind.parse().ok());
+                match (price, kind) {
+                    (Some(price), Some(kind)) => key_levels.push(KeyLevel {
+                        price,
+                        kind,
+                        description: item
+                            .get("description")
+                            .and_then(|v| v.as_str())
+                            .map(str::to_string),
+                    }),
+                    _ => problems.push(format!(
+                        "'key_levels' element {} needs a numeric 'price' and a 'kind' of \"support\" or \"resistance\"",
+                        i
+                    )),
+                }
+            }
+        }
+        None => problems.push("'key_levels' must be an array".to_string()),
+    }
+
+    match (trend, confidence) {
+        (Some(trend), Some(confidence)) if problems.is_empty() => Ok(Analysis {
+            summary,
+            details,
+            recommendations,
+            trend,
+            confidence,
+            key_levels,
+        }),
+        _ => Err(problems.join("; ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars() -> Vec<OHLCV> {
+        (0..40)
+            .map(|i| {
+                let close = 50.0 + (i % 9) as f64 - (i % 4) as f64 * 0.5;
+                OHLCV {
+                    open: close - 0.3,
+                    high: close + 0.8,
+                    low: close - 0.9,
+                    close,
+                    volume: 500.0 + 5.0 * (i % 6) as f64,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chinese_analysis_prompt_keeps_localized_data_info() {
+        let messages = analysis_messages(
+            &bars(),
+            AnalysisType::Comprehensive,
+            None,
+            &AnalysisContext::default(),
+            OhlcvFormat::Json,
+            &LanguagePack::simplified_chinese(),
+        )
+        .unwrap();
+        let system = &messages[0].content;
+        assert!(system.contains("数据特征：\n该数据集包含40个周期的OHLCV数据。"));
+        assert!(system.contains("本地识别出的支撑位和阻力位"));
+        assert!(!system.contains("This dataset"));
+        assert!(!system.contains("detected locally"));
+    }
+
+    const VALID: &str = r#"{"summary": "Steady climb", "details": ["Higher lows"], "recommendations": ["Trail stops"], "trend": "Bullish", "confidence": 0.8, "key_levels": [{"price": 48.5, "kind": "support"}, {"price": 55, "kind": "resistance", "description": "prior high"}]}"#;
+
+    #[test]
+    fn parses_a_fenced_analysis() {
+        let analysis = parse_analysis(&format!("Here you go:\n```json\n{}\n```", VALID)).unwrap();
+        assert_eq!(analysis.trend, TrendDirection::Bullish);
+        assert_eq!(analysis.confidence, 0.8);
+        assert_eq!(analysis.key_levels.len(), 2);
+        assert_eq!(analysis.key_levels[1].kind, LevelKind::Resistance);
+        assert_eq!(
+            analysis.key_levels[1].description.as_deref(),
+            Some("prior high")
+        );
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let error = parse_analysis(
+            r#"{"summary": " ", "details": [], "recommendations": "none", "trend": "up", "confidence": 1.5, "key_levels": [{"price": "high", "kind": "support"}]}"#,
+        )
+        .unwrap_err();
+        for problem in [
+            "'summary' must be a non-empty string",
+            "'details' must not be empty",
+            "'recommendations' must be an array of strings",
+            "'trend' must be one of",
+            "'confidence' must be a number between 0 and 1",
+            "'key_levels' element 0 needs a numeric 'price'",
+        ] {
+            assert!(
+                error.contains(problem),
+                "missing {:?} in {:?}",
+                problem,
+                error
+            );
+        }
+        assert!(parse_analysis("no json here").is_err());
+    }
+
+    #[test]
+    fn response_format_follows_the_model_capabilities() {
+        let schema = analysis_response_format(&[Capability::JsonSchema, Capability::JsonMode]);
+        assert!(matches!(schema, Some(ResponseFormat::JsonSchema { .. })));
+        assert_eq!(
+            analysis_response_format(&[Capability::JsonMode]),
+            Some(ResponseFormat::JsonObject)
+        );
+        assert_eq!(analysis_response_format(&[Capability::Chat]), None);
+    }
+}
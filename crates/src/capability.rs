@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aliyun::model as aliyun_model,
+    deepseek::model as deepseek_model,
+    types::{ModelId, Provider},
+};
+
+/// Context window (in tokens) from which a model counts as long-context.
+pub const LONG_CONTEXT_TOKENS: u32 = 32_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    Chat,
+    Vision,
+    Audio,
+    FunctionCalling,
+    JsonMode,
+    JsonSchema,
+    Streaming,
+    Reasoning,
+    LongContext,
+    Financial,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Chat => "chat",
+            Self::Vision => "vision",
+            Self::Audio => "audio",
+            Self::FunctionCalling => "function-calling",
+            Self::JsonMode => "json-mode",
+            Self::JsonSchema => "json-schema",
+            Self::Streaming => "streaming",
+            Self::Reasoning => "reasoning",
+            Self::LongContext => "long-context",
+            Self::Financial => "financial",
+        }
+    }
+
+    /// Maps a descriptive capability tag from the model catalogs
+    /// (e.g. "complex-reasoning", "image-understanding") to a typed capability.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "chat" | "text-generation" | "qa" | "instruction-following" => Some(Self::Chat),
+            "image-understanding" | "video-analysis" | "multimodal" | "multimodal-reasoning" => {
+                Some(Self::Vision)
+            }
+            "audio-processing" | "audio-analysis" | "audio-chat" | "speech-recognition"
+            | "speech-synthesis" | "voice-assistant" => Some(Self::Audio),
+            "reasoning"
+            | "complex-reasoning"
+            | "logical-analysis"
+            | "problem-solving"
+            | "mathematical-reasoning" => Some(Self::Reasoning),
+            "financial-analysis"
+            | "market-prediction"
+            | "risk-assessment"
+            | "quantitative-analysis"
+            | "portfolio-optimization"
+            | "financial-reporting" => Some(Self::Financial),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for Capability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chat" => Ok(Self::Chat),
+            "vision" => Ok(Self::Vision),
+            "audio" => Ok(Self::Audio),
+            "function-calling" => Ok(Self::FunctionCalling),
+            "json-mode" => Ok(Self::JsonMode),
+            "json-schema" => Ok(Self::JsonSchema),
+            "streaming" => Ok(Self::Streaming),
+            "reasoning" => Ok(Self::Reasoning),
+            "long-context" => Ok(Self::LongContext),
+            "financial" => Ok(Self::Financial),
+            _ => Err(format!("unknown capability '{}'", s)),
+        }
+    }
+}
+
+/// A model from any provider, described with typed capabilities.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: ModelId,
+    pub provider: Provider,
+    pub name: String,
+    pub display_name: String,
+    pub context_length: Option<u32>,
+    /// USD per 1K input tokens; `None` where the provider's price isn't tracked.
+    pub input_cost_per_1k_tokens: Option<f64>,
+    /// USD per 1K output tokens; `None` where the provider's price isn't tracked.
+    pub output_cost_per_1k_tokens: Option<f64>,
+    pub capabilities: Vec<Capability>,
+}
+
+impl ModelInfo {
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+/// Lists the models of every provider.
+pub fn get_all_model_infos() -> Vec<ModelInfo> {
+    let mut infos = Vec::new();
+    for (model_type, model) in aliyun_model::get_aliyun_models() {
+        infos.push(ModelInfo {
+            id: ModelId::Aliyun(model_type),
+            provider: Provider::Aliyun,
+            capabilities: model.typed_capabilities(),
+            name: model.name,
+            display_name: model.display_name,
+            context_length: model.context_length,
+            // Aliyun bills in CNY with per-region tiers; no prices are tracked.
+            input_cost_per_1k_tokens: None,
+            output_cost_per_1k_tokens: None,
+        });
+    }
+    for (model_type, model) in deepseek_model::get_deepseek_models() {
+        infos.push(ModelInfo {
+            id: ModelId::DeepSeek(model_type),
+            provider: Provider::DeepSeek,
+            capabilities: model.typed_capabilities(),
+            name: model.name,
+            display_name: model.display_name,
+            context_length: model.context_length,
+            input_cost_per_1k_tokens: model.input_cost_per_1k_tokens,
+            output_cost_per_1k_tokens: model.output_cost_per_1k_tokens,
+        });
+    }
+    infos
+}
+
+/// Finds models across all providers that support every requested capability.
+///
+/// # Params
+/// - `capabilities`: capabilities the model must all support
+/// - `max_cost`: Optional, maximum input cost in USD per 1K tokens; models without pricing are excluded
+/// - `min_context`: Optional, minimum context length in tokens
+///
+/// Results are ordered from cheapest to most expensive input cost.
+pub fn find_models(
+    capabilities: &[Capability],
+    max_cost: Option<f64>,
+    min_context: Option<u32>,
+) -> Vec<ModelInfo> {
+    let mut models: Vec<ModelInfo> = get_all_model_infos()
+        .into_iter()
+        .filter(|model| capabilities.iter().all(|c| model.supports(*c)))
+        .filter(|model| match max_cost {
+            Some(max_cost) => model
+                .input_cost_per_1k_tokens
+                .is_some_and(|cost| cost <= max_cost),
+            None => true,
+        })
+        .filter(|model| match min_context {
+            Some(min_context) => model.context_length.unwrap_or(0) >= min_context,
+            None => true,
+        })
+        .collect();
+    models.sort_by(|a, b| {
+        let a_cost = a.input_cost_per_1k_tokens.unwrap_or(f64::MAX);
+        let b_cost = b.input_cost_per_1k_tokens.unwrap_or(f64::MAX);
+        a_cost.total_cmp(&b_cost).then_with(|| a.name.cmp(&b.name))
+    });
+    models
+}
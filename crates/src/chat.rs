@@ -0,0 +1,780 @@
+use futures::{StreamExt, stream};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::{
+    fmt::Display,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use crate::{
+    analysis::{
+        Analysis, AnalysisContext, AnalysisType, analysis_messages, analysis_response_format,
+        parse_analysis, structured_analysis_messages,
+    },
+    capability::Capability,
+    encoding::{EncodingContext, PriceEncoding},
+    extract::{
+        extraction_format, extraction_messages, extraction_schema, parse_extraction, retry_messages,
+    },
+    gaps::{FillStrategy, GapFillResult, fill_gaps},
+    health::{
+        AuthStatus, HEALTH_CHAT_PROMPT, HEALTH_CHECK_CONCURRENCY, HEALTH_JSON_PROMPT, HealthReport,
+        is_json_object_reply,
+    },
+    indicators::IndicatorContext,
+    language::{Language, language_pack},
+    prompts::{PredictInputs, PromptTemplate, predict_ohlcv_messages, repair_messages},
+    serialization::OhlcvFormat,
+    types::{
+        Candle, ChatMessage, ChatRole, ErrorKind, ModelId, OHLCV, PredictionFailure,
+        PredictionReport, ResponseFormat, TokenUsage,
+    },
+};
+
+/// The `ChatOptions` fields both providers have, borrowed so the shared request
+/// loops can read and adjust them.
+pub(crate) struct SharedOptions<'a> {
+    pub temperature: &'a mut Option<f32>,
+    pub max_tokens: &'a mut Option<u32>,
+    pub stream: &'a mut bool,
+    pub system_prompt: &'a mut Option<String>,
+    pub encoding: &'a mut PriceEncoding,
+    pub data_format: &'a mut OhlcvFormat,
+    pub indicators: &'a mut Option<IndicatorContext>,
+    pub analysis_context: &'a mut AnalysisContext,
+    pub language: &'a mut Option<Language>,
+    pub prompt_template: &'a mut Option<PromptTemplate>,
+    pub response_format: &'a mut Option<ResponseFormat>,
+    pub max_retries: &'a mut u32,
+    pub gap_fill: &'a mut Option<FillStrategy>,
+}
+
+/// What the provider-independent request loops below need from a client.
+pub(crate) trait ChatClient {
+    type Model: Copy;
+    type Options: Clone + Default;
+    type Error: Display;
+
+    async fn complete(
+        &self,
+        messages: &[ChatMessage],
+        options: Option<Self::Options>,
+    ) -> Result<serde_json::Value, Self::Error>;
+
+    async fn stream(
+        &self,
+        messages: &[ChatMessage],
+        callback: Box<dyn FnMut(String, bool) + Send>,
+        options: Option<Self::Options>,
+    ) -> Result<(), Self::Error>;
+
+    fn content(&self, response: &serde_json::Value) -> Result<String, Self::Error>;
+
+    /// The configured model, used when the options do not name one.
+    fn default_model(&self) -> Self::Model;
+
+    fn available_models() -> Vec<Self::Model>;
+
+    fn model_id(model: Self::Model) -> ModelId;
+
+    /// Typed capabilities of `model`; empty for models missing from the catalog.
+    fn capabilities(model: Self::Model) -> Vec<Capability>;
+
+    fn options_model(options: &Self::Options) -> Option<Self::Model>;
+
+    fn set_options_model(options: &mut Self::Options, model: Self::Model);
+
+    fn shared(options: &mut Self::Options) -> SharedOptions<'_>;
+
+    fn parse_error(message: String) -> Self::Error;
+
+    fn config_error(message: String) -> Self::Error;
+
+    fn error_kind(error: &Self::Error) -> ErrorKind;
+
+    /// Whether the API rejected the request itself (HTTP 400), e.g. its `response_format`.
+    fn is_bad_request(error: &Self::Error) -> bool;
+
+    fn model_for(&self, options: &Self::Options) -> Self::Model {
+        Self::options_model(options).unwrap_or_else(|| self.default_model())
+    }
+}
+
+/// Rejects a `response_format` the model cannot honour instead of letting the API ignore it.
+pub(crate) fn check_response_format(
+    model_name: &str,
+    capabilities: &[Capability],
+    response_format: Option<&ResponseFormat>,
+) -> Result<(), String> {
+    match response_format.and_then(|format| format.required_capability()) {
+        Some(capability) if !capabilities.contains(&capability) => Err(format!(
+            "Model {} does not support the {} response format",
+            model_name,
+            capability.as_str()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Sends a request, stepping `response_format` down from a JSON schema to JSON mode
+/// to none while the API rejects it with a 400. The accepted format is kept in
+/// `options` for follow-up requests.
+async fn complete_with_fallback<C: ChatClient>(
+    client: &C,
+    messages: &[ChatMessage],
+    options: &mut C::Options,
+) -> Result<serde_json::Value, C::Error> {
+    let model = client.model_for(options);
+    loop {
+        let error = match client.complete(messages, Some(options.clone())).await {
+            Err(e) if C::is_bad_request(&e) => e,
+            result => return result,
+        };
+        let response_format = C::shared(options).response_format;
+        *response_format = match response_format.take() {
+            Some(ResponseFormat::JsonSchema { .. })
+                if C::capabilities(model).contains(&Capability::JsonMode) =>
+            {
+                Some(ResponseFormat::JsonObject)
+            }
+            Some(ResponseFormat::JsonSchema { .. } | ResponseFormat::JsonObject) => None,
+            _ => return Err(error),
+        };
+    }
+}
+
+pub(crate) async fn chat<C: ChatClient>(
+    client: &C,
+    message: &str,
+    options: Option<C::Options>,
+) -> Result<String, C::Error> {
+    let mut opts = options.unwrap_or_default();
+    let mut messages = Vec::new();
+    if let Some(system_prompt) = C::shared(&mut opts).system_prompt.clone() {
+        messages.push(ChatMessage {
+            role: ChatRole::System,
+            content: system_prompt,
+        });
+    }
+    messages.push(ChatMessage {
+        role: ChatRole::User,
+        content: message.to_string(),
+    });
+    let response = client.complete(&messages, Some(opts)).await?;
+    client.content(&response)
+}
+
+pub(crate) async fn chat_extract<C: ChatClient, T: DeserializeOwned + JsonSchema>(
+    client: &C,
+    messages: &[ChatMessage],
+    options: Option<C::Options>,
+) -> Result<T, C::Error> {
+    let mut chat_options = options.unwrap_or_else(|| {
+        let mut options = C::Options::default();
+        *C::shared(&mut options).temperature = Some(0.2);
+        options
+    });
+    let schema = extraction_schema::<T>();
+    let model = client.model_for(&chat_options);
+    let shared = C::shared(&mut chat_options);
+    if shared.response_format.is_none() {
+        *shared.response_format = extraction_format::<T>(&schema, &C::capabilities(model));
+    }
+    *shared.stream = false;
+    let max_retries = *shared.max_retries;
+    let mut conversation = extraction_messages(messages, &schema);
+    let mut attempt = 0;
+    loop {
+        let response = complete_with_fallback(client, &conversation, &mut chat_options).await?;
+        let content = client.content(&response)?;
+        match parse_extraction::<T>(&content) {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < max_retries => {
+                attempt += 1;
+                conversation.extend(retry_messages(&content, &e));
+            }
+            Err(e) => {
+                return Err(C::parse_error(format!(
+                    "Invalid reply after {} attempt(s): {}",
+                    attempt + 1,
+                    e
+                )));
+            }
+        }
+    }
+}
+
+/// Probes a model for auth, latency, streaming and JSON replies.
+pub(crate) async fn health_check<C: ChatClient>(client: &C, model: C::Model) -> HealthReport {
+    let model_id = C::model_id(model);
+    let mut options = C::Options::default();
+    C::set_options_model(&mut options, model);
+    *C::shared(&mut options).max_tokens = Some(20);
+    let started = Instant::now();
+    if let Err(e) = chat(client, HEALTH_CHAT_PROMPT, Some(options.clone())).await {
+        return HealthReport::failed(model_id, C::error_kind(&e), e.to_string());
+    }
+    let latency = started.elapsed();
+    let received = Arc::new(AtomicBool::new(false));
+    let received_flag = received.clone();
+    let callback = Box::new(move |chunk: String, done: bool| {
+        if !done && !chunk.is_empty() {
+            received_flag.store(true, Ordering::Relaxed);
+        }
+    });
+    let messages = vec![ChatMessage {
+        role: ChatRole::User,
+        content: HEALTH_CHAT_PROMPT.to_string(),
+    }];
+    let streaming_verified = client
+        .stream(&messages, callback, Some(options.clone()))
+        .await
+        .is_ok()
+        && received.load(Ordering::Relaxed);
+    let mut json_options = options;
+    let shared = C::shared(&mut json_options);
+    *shared.temperature = Some(0.0);
+    *shared.response_format = Some(ResponseFormat::JsonObject);
+    let json_mode_verified = chat(client, HEALTH_JSON_PROMPT, Some(json_options))
+        .await
+        .map(|content| is_json_object_reply(&content))
+        .unwrap_or(false);
+    HealthReport {
+        provider: model_id.provider(),
+        model: model_id,
+        healthy: true,
+        auth: AuthStatus::from_error_kind(None),
+        latency: Some(latency),
+        streaming_verified,
+        json_mode_verified,
+        error_kind: None,
+        error: None,
+    }
+}
+
+/// Probes every available chat model, `HEALTH_CHECK_CONCURRENCY` at a time.
+pub(crate) async fn health_check_all<C: ChatClient>(client: &C) -> Vec<HealthReport> {
+    let mut reports: Vec<HealthReport> = stream::iter(
+        C::available_models()
+            .into_iter()
+            .filter(|&model| C::capabilities(model).contains(&Capability::Chat)),
+    )
+    .map(|model| health_check(client, model))
+    .buffer_unordered(HEALTH_CHECK_CONCURRENCY)
+    .collect()
+    .await;
+    reports.sort_by_key(|report| report.model.as_str());
+    reports
+}
+
+pub(crate) async fn predict_ohlcv_with_report<C: ChatClient>(
+    client: &C,
+    ohlcv_array: &[OHLCV],
+    instructions: Option<&str>,
+    count: Option<usize>,
+    options: Option<C::Options>,
+) -> Result<PredictionReport, PredictionFailure<C::Error>> {
+    predict_with_gaps(client, ohlcv_array, None, instructions, count, options).await
+}
+
+/// `predict_ohlcv_with_report` whose prompt also lists the `gaps` found in the series.
+async fn predict_with_gaps<C: ChatClient>(
+    client: &C,
+    ohlcv_array: &[OHLCV],
+    gaps: Option<&GapFillResult>,
+    instructions: Option<&str>,
+    count: Option<usize>,
+    options: Option<C::Options>,
+) -> Result<PredictionReport, PredictionFailure<C::Error>> {
+    let processed_instructions =
+        instructions.unwrap_or("Based on these OHLCV data, predict the next period");
+    let processed_count = count.unwrap_or(1);
+    if processed_count == 0 {
+        return Err(C::config_error("Count must be positive integer".to_string()).into());
+    }
+    const MAX_COUNT: usize = 50;
+    if processed_count > MAX_COUNT {
+        return Err(C::config_error(format!(
+            "Count parameter too large: {}. Maximum allowed is {}",
+            processed_count, MAX_COUNT
+        ))
+        .into());
+    }
+    let default_max_tokens = *C::shared(&mut C::Options::default()).max_tokens;
+    let mut chat_options = options.unwrap_or_default();
+    let model = client.model_for(&chat_options);
+    let shared = C::shared(&mut chat_options);
+    let data_format = *shared.data_format;
+    let encoding = EncodingContext::new(*shared.encoding, ohlcv_array);
+    let encoded = encoding.encode(ohlcv_array);
+    let data_string = data_format
+        .serialize(&encoded)
+        .map_err(|e| C::parse_error(format!("Failed to serialize OHLCV data: {}", e)))?;
+    let language = shared.language.clone().unwrap_or_default();
+    let pack = language_pack(&language).map_err(C::config_error)?;
+    let indicators = shared
+        .indicators
+        .as_ref()
+        .map(|context| context.prompt_note(ohlcv_array, &pack))
+        .unwrap_or_default();
+    let gaps = gaps
+        .map(|result| result.prompt_note(&pack))
+        .unwrap_or_default();
+    let response_format = match shared.response_format.clone() {
+        Some(ResponseFormat::Text) => None,
+        Some(format) if !data_format.is_json() => {
+            return Err(C::config_error(format!(
+                "The {} response format needs a JSON data format, not {}",
+                format.as_str(),
+                data_format.as_str()
+            ))
+            .into());
+        }
+        Some(format) => Some(format),
+        None => data_format.response_format(processed_count, &C::capabilities(model)),
+    };
+    let messages = predict_ohlcv_messages(
+        shared.prompt_template.as_ref(),
+        &PredictInputs {
+            instructions: processed_instructions,
+            count: processed_count,
+            periods: ohlcv_array.len(),
+            data: &data_string,
+            data_format,
+            encoding: &encoding,
+            indicators: &indicators,
+            gaps: &gaps,
+            wrapped: response_format.is_some(),
+            language: &language,
+            pack: &pack,
+        },
+    )
+    .map_err(|e| C::config_error(e.to_string()))?;
+    let estimated_tokens = (processed_count * 50 + 100) as u32;
+    *shared.max_tokens = Some(
+        shared
+            .max_tokens
+            .or(default_max_tokens)
+            .unwrap_or(1000)
+            .max(estimated_tokens),
+    );
+    *shared.temperature = Some(shared.temperature.unwrap_or(0.3));
+    *shared.system_prompt = None;
+    *shared.response_format = response_format;
+    let max_retries = *shared.max_retries;
+    let mut conversation = messages;
+    let mut report = PredictionReport::default();
+    loop {
+        report.attempts += 1;
+        let content = match complete_with_fallback(client, &conversation, &mut chat_options)
+            .await
+            .and_then(|response| {
+                report.usage.add(&TokenUsage::from_response(&response));
+                client.content(&response)
+            }) {
+            Ok(content) => content,
+            Err(error) => return Err(PredictionFailure { error, report }),
+        };
+        match data_format.parse_response(&content, encoded.last(), processed_count) {
+            Ok(result) => {
+                report.predictions = encoding.decode(&result);
+                return Ok(report);
+            }
+            Err(e) if report.attempts <= max_retries => {
+                conversation.extend(repair_messages(&content, &e, processed_count, data_format));
+                report.rejected.push(e);
+            }
+            Err(e) => {
+                let error = C::parse_error(format!(
+                    "{} (after {} attempt(s), {} tokens used)",
+                    e, report.attempts, report.usage.total_tokens
+                ));
+                report.rejected.push(e);
+                return Err(PredictionFailure { error, report });
+            }
+        }
+    }
+}
+
+pub(crate) async fn analyze_ohlcv<C: ChatClient>(
+    client: &C,
+    ohlcv_array: &[OHLCV],
+    analysis_type: Option<AnalysisType>,
+    message: Option<&str>,
+    options: Option<C::Options>,
+) -> Result<String, C::Error> {
+    let mut chat_options = options.unwrap_or_else(|| analysis_defaults::<C>(0.5, 1500));
+    let shared = C::shared(&mut chat_options);
+    let pack =
+        language_pack(&shared.language.clone().unwrap_or_default()).map_err(C::config_error)?;
+    let messages = analysis_messages(
+        ohlcv_array,
+        analysis_type.unwrap_or_default(),
+        message,
+        shared.analysis_context,
+        *shared.data_format,
+        &pack,
+    )
+    .map_err(C::parse_error)?;
+    *shared.stream = false;
+    *shared.system_prompt = None;
+    let response = client.complete(&messages, Some(chat_options)).await?;
+    client.content(&response)
+}
+
+pub(crate) async fn analyze_ohlcv_structured<C: ChatClient>(
+    client: &C,
+    ohlcv_array: &[OHLCV],
+    analysis_type: Option<AnalysisType>,
+    message: Option<&str>,
+    options: Option<C::Options>,
+) -> Result<Analysis, C::Error> {
+    let mut chat_options = options.unwrap_or_else(|| analysis_defaults::<C>(0.4, 1200));
+    let model = client.model_for(&chat_options);
+    let shared = C::shared(&mut chat_options);
+    let pack =
+        language_pack(&shared.language.clone().unwrap_or_default()).map_err(C::config_error)?;
+    let messages = structured_analysis_messages(
+        ohlcv_array,
+        analysis_type.unwrap_or_default(),
+        message,
+        shared.analysis_context,
+        *shared.data_format,
+        &pack,
+    )
+    .map_err(C::parse_error)?;
+    *shared.stream = false;
+    *shared.system_prompt = None;
+    if shared.response_format.is_none() {
+        *shared.response_format = analysis_response_format(&C::capabilities(model));
+    }
+    let max_retries = *shared.max_retries;
+    let mut conversation = messages;
+    let mut attempt = 0;
+    loop {
+        let response = complete_with_fallback(client, &conversation, &mut chat_options).await?;
+        let content = client.content(&response)?;
+        match parse_analysis(&content) {
+            Ok(analysis) => return Ok(analysis),
+            Err(e) if attempt < max_retries => {
+                attempt += 1;
+                conversation.extend(retry_messages(&content, &e));
+            }
+            Err(e) => {
+                return Err(C::parse_error(format!(
+                    "Invalid structured analysis after {} attempt(s): {}",
+                    attempt + 1,
+                    e
+                )));
+            }
+        }
+    }
+}
+
+pub(crate) async fn predict_candles<C: ChatClient>(
+    client: &C,
+    candles: &[Candle],
+    instructions: Option<&str>,
+    count: Option<usize>,
+    options: Option<C::Options>,
+) -> Result<Vec<Candle>, C::Error> {
+    let (last, step_millis) = candles
+        .last()
+        .zip(Candle::series_step_millis(candles))
+        .ok_or_else(|| {
+            C::config_error(
+                "Unable to detect the candle interval: set `interval` or pass at least two candles"
+                    .to_string(),
+            )
+        })?;
+    let mut options = options.unwrap_or_default();
+    let gaps = C::shared(&mut options)
+        .gap_fill
+        .map(|strategy| fill_gaps(candles, strategy, Some(step_millis)));
+    let series = gaps.as_ref().map_or(candles, |result| &result.candles);
+    let ohlcv_array: Vec<OHLCV> = series.iter().map(OHLCV::from).collect();
+    let predictions = predict_with_gaps(
+        client,
+        &ohlcv_array,
+        gaps.as_ref(),
+        instructions,
+        count,
+        Some(options),
+    )
+    .await
+    .map_err(|failure| failure.error)?
+    .predictions;
+    Ok(Candle::continue_series(last, &predictions, step_millis))
+}
+
+fn analysis_defaults<C: ChatClient>(temperature: f32, max_tokens: u32) -> C::Options {
+    let mut options = C::Options::default();
+    let shared = C::shared(&mut options);
+    *shared.temperature = Some(temperature);
+    *shared.max_tokens = Some(max_tokens);
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deepseek::{ChatOptions, DeepSeekAI, DeepSeekError, DeepSeekModelType};
+    use serde::Deserialize;
+    use std::{collections::VecDeque, sync::Mutex};
+
+    /// Answers each request with the next scripted reply and records the conversation.
+    struct Scripted {
+        replies: Mutex<VecDeque<String>>,
+        requests: Mutex<Vec<Vec<ChatMessage>>>,
+        formats: Mutex<Vec<Option<ResponseFormat>>>,
+    }
+
+    impl Scripted {
+        fn new(replies: &[&str]) -> Self {
+            Self {
+                replies: Mutex::new(replies.iter().map(|reply| reply.to_string()).collect()),
+                requests: Mutex::new(Vec::new()),
+                formats: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn requests(&self) -> Vec<Vec<ChatMessage>> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl ChatClient for Scripted {
+        type Model = DeepSeekModelType;
+        type Options = ChatOptions;
+        type Error = DeepSeekError;
+
+        async fn complete(
+            &self,
+            messages: &[ChatMessage],
+            options: Option<ChatOptions>,
+        ) -> Result<serde_json::Value, DeepSeekError> {
+            self.requests.lock().unwrap().push(messages.to_vec());
+            self.formats
+                .lock()
+                .unwrap()
+                .push(options.and_then(|options| options.response_format));
+            let reply = self.replies.lock().unwrap().pop_front();
+            let reply = reply.ok_or_else(|| DeepSeekError::ApiError("no reply left".into()))?;
+            Ok(serde_json::json!({ "choices": [{ "message": { "content": reply } }] }))
+        }
+
+        async fn stream(
+            &self,
+            _messages: &[ChatMessage],
+            _callback: Box<dyn FnMut(String, bool) + Send>,
+            _options: Option<ChatOptions>,
+        ) -> Result<(), DeepSeekError> {
+            Ok(())
+        }
+
+        fn content(&self, response: &serde_json::Value) -> Result<String, DeepSeekError> {
+            Ok(response["choices"][0]["message"]["content"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string())
+        }
+
+        fn default_model(&self) -> DeepSeekModelType {
+            DeepSeekModelType::DeepSeekChat
+        }
+
+        fn available_models() -> Vec<DeepSeekModelType> {
+            DeepSeekAI::available_models()
+        }
+
+        fn model_id(model: DeepSeekModelType) -> ModelId {
+            DeepSeekAI::model_id(model)
+        }
+
+        fn capabilities(model: DeepSeekModelType) -> Vec<Capability> {
+            DeepSeekAI::capabilities(model)
+        }
+
+        fn options_model(options: &ChatOptions) -> Option<DeepSeekModelType> {
+            DeepSeekAI::options_model(options)
+        }
+
+        fn set_options_model(options: &mut ChatOptions, model: DeepSeekModelType) {
+            DeepSeekAI::set_options_model(options, model)
+        }
+
+        fn shared(options: &mut ChatOptions) -> SharedOptions<'_> {
+            DeepSeekAI::shared(options)
+        }
+
+        fn parse_error(message: String) -> DeepSeekError {
+            DeepSeekAI::parse_error(message)
+        }
+
+        fn config_error(message: String) -> DeepSeekError {
+            DeepSeekAI::config_error(message)
+        }
+
+        fn error_kind(error: &DeepSeekError) -> ErrorKind {
+            DeepSeekAI::error_kind(error)
+        }
+
+        fn is_bad_request(_error: &DeepSeekError) -> bool {
+            false
+        }
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema)]
+    struct Quote {
+        price: f64,
+    }
+
+    fn user(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage {
+            role: ChatRole::User,
+            content: content.to_string(),
+        }]
+    }
+
+    #[tokio::test]
+    async fn chat_extract_retries_by_default() {
+        let client = Scripted::new(&["no idea", "still prose", r#"{"price": 1.5}"#]);
+        let quote: Quote = chat_extract(&client, &user("Quote it"), None)
+            .await
+            .unwrap();
+        assert_eq!(quote.price, 1.5);
+        let requests = client.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].len() > requests[0].len());
+    }
+
+    #[tokio::test]
+    async fn chat_extract_gives_up_when_retries_are_disabled() {
+        let client = Scripted::new(&["no idea", r#"{"price": 1.5}"#]);
+        let options = ChatOptions {
+            max_retries: 0,
+            ..Default::default()
+        };
+        let result = chat_extract::<_, Quote>(&client, &user("Quote it"), Some(options)).await;
+        assert!(matches!(result, Err(DeepSeekError::ParseError(_))));
+        assert_eq!(client.requests().len(), 1);
+    }
+
+    fn bars() -> Vec<OHLCV> {
+        [
+            (10.0, 10.5, 9.5, 10.0, 100.0),
+            (10.0, 11.5, 9.8, 11.0, 200.0),
+            (11.0, 12.4, 10.9, 12.0, 150.0),
+        ]
+        .iter()
+        .map(|&(open, high, low, close, volume)| OHLCV {
+            open,
+            high,
+            low,
+            close,
+            volume,
+        })
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn predict_re_prompts_an_invalid_reply_by_default() {
+        let client = Scripted::new(&[
+            r#"{"predictions": [{"open": 12, "high": 11, "low": 10, "close": 11, "volume": 90}]}"#,
+            r#"{"predictions": [{"open": 12, "high": 12.5, "low": 11.5, "close": 12.2, "volume": 90}]}"#,
+        ]);
+        let report = predict_ohlcv_with_report(&client, &bars(), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(report.attempts, 2);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.predictions[0].close, 12.2);
+        let requests = client.requests();
+        assert!(requests[1].len() > requests[0].len());
+    }
+
+    #[tokio::test]
+    async fn predict_candles_fills_gaps_and_lists_them_in_the_prompt() {
+        let minute = 60_000;
+        let candles: Vec<Candle> = [0, 1, 4, 5]
+            .iter()
+            .zip(bars().iter().cycle())
+            .map(|(&index, ohlcv)| Candle::from_ohlcv(index * minute, ohlcv))
+            .collect();
+        let client = Scripted::new(&[
+            r#"{"predictions": [{"open": 12, "high": 12.5, "low": 11.5, "close": 12.2, "volume": 90}]}"#,
+        ]);
+        let options = ChatOptions {
+            gap_fill: Some(FillStrategy::ForwardFill),
+            ..Default::default()
+        };
+        let predicted = predict_candles(&client, &candles, None, None, Some(options))
+            .await
+            .unwrap();
+        assert_eq!(predicted[0].timestamp, 6 * minute);
+        let prompt = &client.requests()[0][1].content;
+        assert!(prompt.contains("6 periods"));
+        assert!(prompt.contains("2 missing period(s) in 1 gap(s) were filled"));
+    }
+
+    #[tokio::test]
+    async fn predict_candles_sends_the_series_as_is_without_gap_fill() {
+        let minute = 60_000;
+        let candles: Vec<Candle> = [0, 1, 4]
+            .iter()
+            .zip(bars().iter())
+            .map(|(&index, ohlcv)| Candle::from_ohlcv(index * minute, ohlcv))
+            .collect();
+        let client = Scripted::new(&[
+            r#"{"predictions": [{"open": 12, "high": 12.5, "low": 11.5, "close": 12.2, "volume": 90}]}"#,
+        ]);
+        predict_candles(&client, &candles, None, None, None)
+            .await
+            .unwrap();
+        let prompt = &client.requests()[0][1].content;
+        assert!(prompt.contains("3 periods"));
+        assert!(!prompt.contains("missing"));
+    }
+
+    #[tokio::test]
+    async fn structured_analysis_asks_for_json_and_re_prompts_with_the_problems() {
+        let client = Scripted::new(&[
+            r#"{"summary": "Up", "details": [], "recommendations": [], "trend": "up", "confidence": 0.7, "key_levels": []}"#,
+            r#"{"summary": "Up", "details": ["Higher highs"], "recommendations": [], "trend": "bullish", "confidence": 0.7, "key_levels": []}"#,
+        ]);
+        let analysis = analyze_ohlcv_structured(&client, &bars(), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(analysis.details, vec!["Higher highs"]);
+        let requests = client.requests();
+        assert_eq!(requests.len(), 2);
+        let retry = &requests[1].last().unwrap().content;
+        assert!(retry.contains("'details' must not be empty"));
+        assert!(retry.contains("'trend' must be one of"));
+        let formats = client.formats.lock().unwrap().clone();
+        let expected =
+            analysis_response_format(&DeepSeekAI::capabilities(DeepSeekModelType::DeepSeekChat));
+        assert!(expected.is_some());
+        assert_eq!(formats[0], expected);
+    }
+
+    #[tokio::test]
+    async fn health_depends_on_the_chat_probe_only() {
+        let client = Scripted::new(&["OK", "not json"]);
+        let report = health_check(&client, DeepSeekModelType::DeepSeekChat).await;
+        assert!(report.healthy);
+        assert_eq!(report.auth, AuthStatus::Valid);
+        assert!(!report.streaming_verified);
+        assert!(!report.json_mode_verified);
+
+        let failing = Scripted::new(&[]);
+        let report = health_check(&failing, DeepSeekModelType::DeepSeekChat).await;
+        assert!(!report.healthy);
+        assert_eq!(report.error_kind, Some(ErrorKind::Api));
+        assert_eq!(report.auth, AuthStatus::Unknown);
+    }
+}
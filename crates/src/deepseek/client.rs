@@ -1,16 +1,29 @@
 use futures::StreamExt;
 use reqwest::Client as HttpClient;
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, de::DeserializeOwned};
 use serde_json::json;
 use std::time::Duration;
 
 use crate::{
+    analysis::{Analysis, AnalysisContext, AnalysisType},
+    capability::Capability,
+    chat::{self, ChatClient, SharedOptions},
     deepseek::{
-        DeepSeekError, DeepSeekModelType, get_model,
-        model::{ApiFormat, Model},
+        DeepSeekError, DeepSeekModelType, get_available_model_types, get_model, model::Model,
         types::DeepSeekStreamCallback,
     },
-    types::{ChatMessage, ChatRole, OHLCV},
+    encoding::PriceEncoding,
+    gaps::FillStrategy,
+    health::HealthReport,
+    indicators::IndicatorContext,
+    language::{Language, apply_language},
+    prompts::PromptTemplate,
+    serialization::OhlcvFormat,
+    types::{
+        Candle, ChatMessage, ErrorKind, ModelId, OHLCV, PredictionFailure, PredictionReport,
+        ResponseFormat,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -49,6 +62,28 @@ pub struct ChatOptions {
     pub stop: Option<Vec<String>>,
     pub logprobs: Option<bool>,
     pub top_logprobs: Option<u32>,
+    /// Price representation used by `predict_ohlcv` prompts.
+    pub encoding: PriceEncoding,
+    /// Layout of the OHLCV rows in `predict_ohlcv` prompts and replies.
+    pub data_format: OhlcvFormat,
+    /// Indicators and summary stats appended to `predict_ohlcv` prompts.
+    pub indicators: Option<IndicatorContext>,
+    /// Patterns and levels computed locally for `analyze_ohlcv` prompts.
+    pub analysis_context: AnalysisContext,
+    /// Response language; localizes analysis prompts and appends a response
+    /// instruction to the system prompt. `None` leaves messages untouched.
+    pub language: Option<Language>,
+    /// Overrides the registered `predict_ohlcv` prompt template for this call.
+    pub prompt_template: Option<PromptTemplate>,
+    /// Constrains the reply format. `predict_ohlcv` picks the strictest format the
+    /// model supports when this is `None`; `Some(ResponseFormat::Text)` opts out.
+    pub response_format: Option<ResponseFormat>,
+    /// Times `chat_extract` and `predict_ohlcv` re-ask with the error after an
+    /// unusable reply. Each retry is a billed request; `0` disables them.
+    pub max_retries: u32,
+    /// How `predict_candles` treats missing bars. With a strategy, the gaps are
+    /// filled before prompting and listed in the prompt; `None` sends the series as is.
+    pub gap_fill: Option<FillStrategy>,
 }
 
 impl Default for ChatOptions {
@@ -65,6 +100,15 @@ impl Default for ChatOptions {
             stop: None,
             logprobs: None,
             top_logprobs: None,
+            encoding: PriceEncoding::Raw,
+            data_format: OhlcvFormat::Json,
+            indicators: None,
+            analysis_context: AnalysisContext::default(),
+            language: None,
+            prompt_template: None,
+            response_format: None,
+            max_retries: 2,
+            gap_fill: None,
         }
     }
 }
@@ -154,20 +198,7 @@ impl DeepSeekAI {
         message: &str,
         options: Option<ChatOptions>,
     ) -> Result<String, DeepSeekError> {
-        let opts = options.unwrap_or_default();
-        let mut messages = Vec::new();
-        if let Some(system_prompt) = &opts.system_prompt {
-            messages.push(ChatMessage {
-                role: ChatRole::System,
-                content: system_prompt.clone(),
-            });
-        }
-        messages.push(ChatMessage {
-            role: ChatRole::User,
-            content: message.to_string(),
-        });
-        let response = self.chat_completion(&messages, Some(opts)).await?;
-        self.extract_content(&response)
+        chat::chat(self, message, options).await
     }
 
     pub async fn chat_completion(
@@ -176,12 +207,19 @@ impl DeepSeekAI {
         options: Option<ChatOptions>,
     ) -> Result<serde_json::Value, DeepSeekError> {
         let opts = options.unwrap_or_default();
+        let messages = match &opts.language {
+            Some(language) => {
+                &apply_language(messages, language).map_err(DeepSeekError::ConfigError)?
+            }
+            None => messages,
+        };
         let model_type = opts.model_type.unwrap_or(self.config.model_type);
         let model = get_model(model_type)
             .ok_or_else(|| DeepSeekError::ModelNotSupported(format!("{:?}", model_type)))?;
         if opts.stream && !model.supports_streaming {
             return Err(DeepSeekError::StreamingNotSupported);
         }
+        Self::check_response_format(&model, opts.response_format.as_ref())?;
         let request_data = self.build_request(&model.name, messages, &opts);
         let response = self
             .make_request(&model.endpoint, &request_data, opts.stream)
@@ -196,12 +234,19 @@ impl DeepSeekAI {
         options: Option<ChatOptions>,
     ) -> Result<(), DeepSeekError> {
         let opts = options.unwrap_or_default();
+        let messages = match &opts.language {
+            Some(language) => {
+                &apply_language(messages, language).map_err(DeepSeekError::ConfigError)?
+            }
+            None => messages,
+        };
         let model_type = opts.model_type.unwrap_or(self.config.model_type);
         let model = get_model(model_type)
             .ok_or_else(|| DeepSeekError::ModelNotSupported(format!("{:?}", model_type)))?;
         if !model.supports_streaming {
             return Err(DeepSeekError::StreamingNotSupported);
         }
+        Self::check_response_format(&model, opts.response_format.as_ref())?;
         let request_data = self.build_request(
             &model.name,
             messages,
@@ -215,6 +260,18 @@ impl DeepSeekAI {
         Ok(())
     }
 
+    /// Asks for a reply matching `T`'s JSON schema and deserializes it. The schema is
+    /// added to the system prompt and, when the model supports it, sent as the
+    /// `response_format`. Unusable replies are retried up to `max_retries` times
+    /// (twice by default) with the error appended to the conversation.
+    pub async fn chat_extract<T: DeserializeOwned + JsonSchema>(
+        &self,
+        messages: &[ChatMessage],
+        options: Option<ChatOptions>,
+    ) -> Result<T, DeepSeekError> {
+        chat::chat_extract(self, messages, options).await
+    }
+
     pub fn set_model(&mut self, model_type: DeepSeekModelType) -> Result<(), DeepSeekError> {
         let model = get_model(model_type)
             .ok_or_else(|| DeepSeekError::ModelNotSupported(format!("{:?}", model_type)))?;
@@ -249,6 +306,19 @@ impl DeepSeekAI {
         }
     }
 
+    /// Probes a model (the configured one by default) for auth, latency, streaming and JSON replies.
+    pub async fn health_check(&self, model_type: Option<DeepSeekModelType>) -> HealthReport {
+        chat::health_check(self, model_type.unwrap_or(self.config.model_type)).await
+    }
+
+    /// Probes every available chat model, a few at a time.
+    pub async fn health_check_all(&self) -> Vec<HealthReport> {
+        chat::health_check_all(self).await
+    }
+
+    /// Predicts the next `count` bars (1 by default, at most 50). A reply that fails
+    /// validation is re-prompted with the problems found, up to `max_retries` times
+    /// (twice by default); set `max_retries: 0` to accept only the first reply.
     pub async fn predict_ohlcv(
         &self,
         ohlcv_array: &[OHLCV],
@@ -256,97 +326,73 @@ impl DeepSeekAI {
         count: Option<usize>,
         options: Option<ChatOptions>,
     ) -> Result<Vec<OHLCV>, DeepSeekError> {
-        let processed_instructions =
-            instructions.unwrap_or("Based on these OHLCV data, predict the next period");
-        let processed_count = count.unwrap_or(1);
-        if processed_count == 0 {
-            return Err(DeepSeekError::ConfigError(
-                "Count must be positive integer".to_string(),
-            ));
-        }
-        const MAX_COUNT: usize = 50;
-        if processed_count > MAX_COUNT {
-            return Err(DeepSeekError::ConfigError(format!(
-                "Count parameter too large: {}. Maximum allowed is {}",
-                processed_count, MAX_COUNT
-            )));
-        }
-        let count_message = if processed_count == 1 {
-            "Return EXACTLY 1 OHLCV object for the next period.".to_string()
-        } else {
-            format!(
-                "Return EXACTLY {} consecutive OHLCV objects for the next {} periods.",
-                processed_count, processed_count
-            )
-        };
-        let system_prompt = format!(
-            r#"You are a professional financial data analysis AI. The user will give you an array of OHLCV (Open, High, Low, Close, Volume) data.
-Your task: {}
-CRITICAL RULES:
-1. {}
-2. Return ONLY a JSON array of OHLCV objects, NO explanations, comments, or other text
-3. The OHLCV array format must match: [{{open, high, low, close, volume}}, ...]
-4. All numbers must be valid numbers
-5. Ensure technical rationality (high >= low, high >= close >= low, volume >= 0)
-6. Maintain consistency with historical trends and patterns
-7. For technical analysis, provide reasonable values based on typical patterns
-8. Do not include markdown formatting, only pure JSON
-{}"#,
-            processed_instructions,
-            count_message,
-            if processed_count == 1 {
-                r#"Example of valid response for 1 period:
-[{"open": 115.5, "high": 118.0, "low": 114.0, "close": 117.0, "volume": 1350000}]"#
-            } else {
-                &format!(
-                    r#"Example of valid response for {} periods:
-[
-  {{"open": 115.5, "high": 118.0, "low": 114.0, "close": 117.0, "volume": 1350000}},
-  {{"open": 117.5, "high": 120.0, "low": 116.0, "close": 119.0, "volume": 1400000}}
-  {} more OHLCV objects following the same pattern
-]"#,
-                    processed_count,
-                    if processed_count > 2 {
-                        format!("{}", processed_count - 2)
-                    } else {
-                        String::new()
-                    }
-                )
-            }
-        );
-        let data_string = serde_json::to_string_pretty(ohlcv_array).map_err(|e| {
-            DeepSeekError::ParseError(format!("Failed to serialize OHLCV data: {}", e))
-        })?;
-        let user_message = format!(
-            "Here is the historical OHLCV data ({} periods):\n{}\nPlease process this data according to the system instructions. Remember to return EXACTLY {} OHLCV object(s) in a JSON array with no additional text.",
-            ohlcv_array.len(),
-            data_string,
-            processed_count
-        );
-        let messages = vec![
-            ChatMessage {
-                role: ChatRole::System,
-                content: system_prompt,
-            },
-            ChatMessage {
-                role: ChatRole::User,
-                content: user_message,
-            },
-        ];
-        let estimated_tokens = (processed_count * 50 + 100) as u32;
-        let max_tokens = options
-            .as_ref()
-            .and_then(|o| o.max_tokens)
-            .unwrap_or(2000)
-            .max(estimated_tokens);
-        let mut chat_options = options.unwrap_or_default();
-        chat_options.max_tokens = Some(max_tokens);
-        chat_options.temperature = Some(chat_options.temperature.unwrap_or(0.3));
-        chat_options.system_prompt = None;
-        let response = self.chat_completion(&messages, Some(chat_options)).await?;
-        let content = self.extract_content(&response)?;
-        let result = self.parse_ohlcv_response(&content, processed_count)?;
-        Ok(result)
+        Ok(self
+            .predict_ohlcv_with_report(ohlcv_array, instructions, count, options)
+            .await
+            .map_err(|failure| failure.error)?
+            .predictions)
+    }
+
+    /// `predict_ohlcv` that also reports the attempts and tokens used. A reply that
+    /// fails validation is sent back with the error, up to `max_retries` times. On
+    /// failure the report of the attempts made comes with the error.
+    pub async fn predict_ohlcv_with_report(
+        &self,
+        ohlcv_array: &[OHLCV],
+        instructions: Option<&str>,
+        count: Option<usize>,
+        options: Option<ChatOptions>,
+    ) -> Result<PredictionReport, PredictionFailure<DeepSeekError>> {
+        chat::predict_ohlcv_with_report(self, ohlcv_array, instructions, count, options).await
+    }
+
+    /// Analyzes OHLCV data and returns the model's text analysis.
+    /// Summary statistics, candlestick patterns and support/resistance levels are
+    /// computed locally and embedded in the prompt.
+    pub async fn analyze_ohlcv(
+        &self,
+        ohlcv_array: &[OHLCV],
+        analysis_type: Option<AnalysisType>,
+        message: Option<&str>,
+        options: Option<ChatOptions>,
+    ) -> Result<String, DeepSeekError> {
+        chat::analyze_ohlcv(self, ohlcv_array, analysis_type, message, options).await
+    }
+
+    /// Like `analyze_ohlcv`, but asks for JSON and validates it into an `Analysis`. The
+    /// schema or JSON mode is requested when the model supports it. Replies that deviate
+    /// from the expected shape are sent back with every problem found, up to
+    /// `max_retries` times, before failing with a `ParseError`.
+    pub async fn analyze_ohlcv_structured(
+        &self,
+        ohlcv_array: &[OHLCV],
+        analysis_type: Option<AnalysisType>,
+        message: Option<&str>,
+        options: Option<ChatOptions>,
+    ) -> Result<Analysis, DeepSeekError> {
+        chat::analyze_ohlcv_structured(self, ohlcv_array, analysis_type, message, options).await
+    }
+
+    /// Predicts the next candles of a timestamped series.
+    /// Predicted candles continue the input timestamps at the series interval. Set
+    /// `gap_fill` to fill missing bars and point them out in the prompt.
+    pub async fn predict_candles(
+        &self,
+        candles: &[Candle],
+        instructions: Option<&str>,
+        count: Option<usize>,
+        options: Option<ChatOptions>,
+    ) -> Result<Vec<Candle>, DeepSeekError> {
+        chat::predict_candles(self, candles, instructions, count, options).await
+    }
+
+    /// Rejects a `response_format` the model cannot honour instead of letting the API ignore it.
+    fn check_response_format(
+        model: &Model,
+        response_format: Option<&ResponseFormat>,
+    ) -> Result<(), DeepSeekError> {
+        chat::check_response_format(&model.name, &model.typed_capabilities(), response_format)
+            .map_err(DeepSeekError::ConfigError)
     }
 
     fn build_request(
@@ -374,6 +420,9 @@ CRITICAL RULES:
         if let Some(top_logprobs) = options.top_logprobs {
             request["top_logprobs"] = json!(top_logprobs);
         }
+        if let Some(response_format) = &options.response_format {
+            request["response_format"] = response_format.to_request_value();
+        }
         request
     }
 
@@ -467,6 +516,16 @@ CRITICAL RULES:
             .await
             .map_err(|e| DeepSeekError::NetworkError(format!("HTTP request failed: {}", e)))?;
         let status = response.status();
+        if status == 429 {
+            return Err(DeepSeekError::RateLimitError(
+                "Rate limit exceeded".to_string(),
+            ));
+        }
+        if status == 401 {
+            return Err(DeepSeekError::AuthError(
+                "Authentication failed. Please check your API key.".to_string(),
+            ));
+        }
         if !status.is_success() {
             let response_clone = response;
             let error_text = response_clone
@@ -493,24 +552,16 @@ CRITICAL RULES:
                 buffer.clear();
             }
             for line in lines.iter().take(lines.len().saturating_sub(1)) {
-                if line.starts_with("data: ") {
-                    let data = &line[6..];
+                if let Some(data) = line.strip_prefix("data: ") {
                     if data == "[DONE]" {
                         callback("".to_string(), true);
                         return Ok(());
                     }
                     match serde_json::from_str::<serde_json::Value>(data) {
                         Ok(parsed) => {
-                            if let Some(choices) = parsed.get("choices") {
-                                if let Some(choice) = choices.get(0) {
-                                    if let Some(delta) = choice.get("delta") {
-                                        if let Some(content) = delta.get("content") {
-                                            if let Some(content_str) = content.as_str() {
-                                                callback(content_str.to_string(), false);
-                                            }
-                                        }
-                                    }
-                                }
+                            if let Some(content) = parsed["choices"][0]["delta"]["content"].as_str()
+                            {
+                                callback(content.to_string(), false);
                             }
                         }
                         Err(e) => {
@@ -535,79 +586,6 @@ CRITICAL RULES:
         }
     }
 
-    fn parse_ohlcv_response(
-        &self,
-        content: &str,
-        expected_count: usize,
-    ) -> Result<Vec<OHLCV>, DeepSeekError> {
-        // Extract JSON array from response
-        let json_match = regex::Regex::new(r"\[[\s\S]*\]")
-            .unwrap()
-            .find(content)
-            .map(|m| m.as_str());
-        let json_str = json_match.unwrap_or(content);
-        let parsed: Vec<serde_json::Value> = serde_json::from_str(json_str)
-            .map_err(|e| DeepSeekError::ParseError(format!("Failed to parse JSON: {}", e)))?;
-        let mut result = Vec::new();
-        for (i, item) in parsed.iter().enumerate() {
-            let obj = item.as_object().ok_or_else(|| {
-                DeepSeekError::ParseError(format!("Element {} is not an object", i))
-            })?;
-            let open = obj.get("open").and_then(|v| v.as_f64()).ok_or_else(|| {
-                DeepSeekError::ParseError(format!("Element {} missing or invalid 'open' field", i))
-            })?;
-            let high = obj.get("high").and_then(|v| v.as_f64()).ok_or_else(|| {
-                DeepSeekError::ParseError(format!("Element {} missing or invalid 'high' field", i))
-            })?;
-            let low = obj.get("low").and_then(|v| v.as_f64()).ok_or_else(|| {
-                DeepSeekError::ParseError(format!("Element {} missing or invalid 'low' field", i))
-            })?;
-            let close = obj.get("close").and_then(|v| v.as_f64()).ok_or_else(|| {
-                DeepSeekError::ParseError(format!("Element {} missing or invalid 'close' field", i))
-            })?;
-            let volume = obj.get("volume").and_then(|v| v.as_f64()).ok_or_else(|| {
-                DeepSeekError::ParseError(format!(
-                    "Element {} missing or invalid 'volume' field",
-                    i
-                ))
-            })?;
-            // Validate OHLCV data
-            if high < low {
-                return Err(DeepSeekError::ParseError(format!(
-                    "Element {}: high cannot be lower than low",
-                    i
-                )));
-            }
-            if close < low || close > high {
-                return Err(DeepSeekError::ParseError(format!(
-                    "Element {}: close must be between low and high",
-                    i
-                )));
-            }
-            if volume < 0.0 {
-                return Err(DeepSeekError::ParseError(format!(
-                    "Element {}: volume must be non-negative",
-                    i
-                )));
-            }
-            result.push(OHLCV {
-                open,
-                high,
-                low,
-                close,
-                volume,
-            });
-        }
-        if result.len() != expected_count {
-            return Err(DeepSeekError::ParseError(format!(
-                "AI returned {} OHLCV objects, but expected {}",
-                result.len(),
-                expected_count
-            )));
-        }
-        Ok(result)
-    }
-
     // Additional DeepSeek specific methods
     pub async fn get_models(&self) -> Result<Vec<Model>, DeepSeekError> {
         // DeepSeek doesn't have a models endpoint like OpenAI,
@@ -649,3 +627,90 @@ CRITICAL RULES:
         Ok(results)
     }
 }
+
+impl ChatClient for DeepSeekAI {
+    type Model = DeepSeekModelType;
+    type Options = ChatOptions;
+    type Error = DeepSeekError;
+
+    async fn complete(
+        &self,
+        messages: &[ChatMessage],
+        options: Option<ChatOptions>,
+    ) -> Result<serde_json::Value, DeepSeekError> {
+        self.chat_completion(messages, options).await
+    }
+
+    async fn stream(
+        &self,
+        messages: &[ChatMessage],
+        callback: DeepSeekStreamCallback,
+        options: Option<ChatOptions>,
+    ) -> Result<(), DeepSeekError> {
+        self.chat_stream(messages, callback, options).await
+    }
+
+    fn content(&self, response: &serde_json::Value) -> Result<String, DeepSeekError> {
+        self.extract_content(response)
+    }
+
+    fn default_model(&self) -> DeepSeekModelType {
+        self.config.model_type
+    }
+
+    fn available_models() -> Vec<DeepSeekModelType> {
+        get_available_model_types()
+    }
+
+    fn model_id(model: DeepSeekModelType) -> ModelId {
+        ModelId::DeepSeek(model)
+    }
+
+    fn capabilities(model: DeepSeekModelType) -> Vec<Capability> {
+        get_model(model)
+            .map(|model| model.typed_capabilities())
+            .unwrap_or_default()
+    }
+
+    fn options_model(options: &ChatOptions) -> Option<DeepSeekModelType> {
+        options.model_type
+    }
+
+    fn set_options_model(options: &mut ChatOptions, model: DeepSeekModelType) {
+        options.model_type = Some(model);
+    }
+
+    fn shared(options: &mut ChatOptions) -> SharedOptions<'_> {
+        SharedOptions {
+            temperature: &mut options.temperature,
+            max_tokens: &mut options.max_tokens,
+            stream: &mut options.stream,
+            system_prompt: &mut options.system_prompt,
+            encoding: &mut options.encoding,
+            data_format: &mut options.data_format,
+            indicators: &mut options.indicators,
+            analysis_context: &mut options.analysis_context,
+            language: &mut options.language,
+            prompt_template: &mut options.prompt_template,
+            response_format: &mut options.response_format,
+            max_retries: &mut options.max_retries,
+            gap_fill: &mut options.gap_fill,
+        }
+    }
+
+    fn parse_error(message: String) -> DeepSeekError {
+        DeepSeekError::ParseError(message)
+    }
+
+    fn config_error(message: String) -> DeepSeekError {
+        DeepSeekError::ConfigError(message)
+    }
+
+    fn error_kind(error: &DeepSeekError) -> ErrorKind {
+        error.kind()
+    }
+
+    fn is_bad_request(error: &DeepSeekError) -> bool {
+        matches!(error, DeepSeekError::ApiError(message) if message.starts_with("HTTP 400"))
+    }
+}
@@ -2,8 +2,8 @@
 //! ### Example 1: Basic Dialogue
 //!
 //! ```rust,no_run
-//! use deepseek_sdk::DeepSeekAI;
-//! use deepseek_sdk::model::DeepSeekModelType;
+//! use ohlcv_ai::deepseek::DeepSeekAI;
+//! use ohlcv_ai::deepseek::model::DeepSeekModelType;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -17,7 +17,8 @@
 //! ### Example 2: Streaming Dialogue
 //!
 //! ```rust,no_run
-//! use deepseek_sdk::{DeepSeekAI, DeepSeekStreamCallback};
+//! use ohlcv_ai::deepseek::{DeepSeekAI, types::DeepSeekStreamCallback};
+//! use ohlcv_ai::types::{ChatMessage, ChatRole};
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -47,9 +48,9 @@
 //! ### Example 3: Financial Forecasting
 //!
 //! ```rust,no_run
-//! use deepseek_sdk::{DeepSeekAI, DeepSeekConfig, ChatOptions};
-//! use deepseek_sdk::model::DeepSeekModelType;
-//! use deepseek_sdk::types::OHLCV;
+//! use ohlcv_ai::deepseek::{DeepSeekAI, DeepSeekConfig, ChatOptions};
+//! use ohlcv_ai::deepseek::model::DeepSeekModelType;
+//! use ohlcv_ai::types::OHLCV;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -83,8 +84,8 @@
 //! ### Example 4: Custom Configuration
 //!
 //! ```rust,no_run
-//! use deepseek_sdk::{DeepSeekAI, DeepSeekConfig, ChatOptions};
-//! use deepseek_sdk::model::DeepSeekModelType;
+//! use ohlcv_ai::deepseek::{DeepSeekAI, DeepSeekConfig, ChatOptions};
+//! use ohlcv_ai::deepseek::model::DeepSeekModelType;
 //! use std::time::Duration;
 //!
 //! #[tokio::main]
@@ -128,12 +129,13 @@ pub use types::DeepSeekError;
 ///
 /// # Example
 /// ```rust
-/// use deepseek_sdk::{create_deepseek_ai, DeepSeekModelType};
+/// use ohlcv_ai::deepseek::{create_deepseek_ai, DeepSeekModelType};
 ///
 /// let ai = create_deepseek_ai(
 ///     "your-api-key".to_string(),
 ///     Some(DeepSeekModelType::DeepSeekCoder)
 /// )?;
+/// # Ok::<(), ohlcv_ai::deepseek::DeepSeekError>(())
 /// ```
 pub fn create_deepseek_ai(
     api_key: String,
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::capability::{Capability, LONG_CONTEXT_TOKENS, find_models};
+use crate::types::ModelId;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DeepSeekModelType {
     DeepSeekChat,
@@ -28,19 +31,23 @@ impl DeepSeekModelType {
             Self::DeepSeekOmni => "deepseek-omni",
         }
     }
-    
-    pub fn from_str(s: &str) -> Option<Self> {
+}
+
+impl std::str::FromStr for DeepSeekModelType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "deepseek-chat" => Some(Self::DeepSeekChat),
-            "deepseek-coder" => Some(Self::DeepSeekCoder),
-            "deepseek-reasoner" => Some(Self::DeepSeekReasoner),
-            "deepseek-math" => Some(Self::DeepSeekMath),
-            "deepseek-financial" => Some(Self::DeepSeekFinancial),
-            "deepseek-medical" => Some(Self::DeepSeekMedical),
-            "deepseek-creative" => Some(Self::DeepSeekCreative),
-            "deepseek-enterprise" => Some(Self::DeepSeekEnterprise),
-            "deepseek-omni" => Some(Self::DeepSeekOmni),
-            _ => None,
+            "deepseek-chat" => Ok(Self::DeepSeekChat),
+            "deepseek-coder" => Ok(Self::DeepSeekCoder),
+            "deepseek-reasoner" => Ok(Self::DeepSeekReasoner),
+            "deepseek-math" => Ok(Self::DeepSeekMath),
+            "deepseek-financial" => Ok(Self::DeepSeekFinancial),
+            "deepseek-medical" => Ok(Self::DeepSeekMedical),
+            "deepseek-creative" => Ok(Self::DeepSeekCreative),
+            "deepseek-enterprise" => Ok(Self::DeepSeekEnterprise),
+            "deepseek-omni" => Ok(Self::DeepSeekOmni),
+            _ => Err(format!("unknown DeepSeek model '{}'", s)),
         }
     }
 }
@@ -61,12 +68,52 @@ pub struct Model {
     pub description: Option<String>,
     pub max_tokens: Option<u32>,
     pub context_length: Option<u32>,
+    /// USD per 1K input tokens (cache miss), from
+    /// <https://api-docs.deepseek.com/quick_start/pricing> as of 2025-02.
+    pub input_cost_per_1k_tokens: Option<f64>,
+    /// USD per 1K output tokens, same source and date as the input cost.
+    pub output_cost_per_1k_tokens: Option<f64>,
     pub capabilities: Vec<String>,
     pub is_free_tier: bool,
     pub supports_streaming: bool,
     pub supports_function_calling: bool,
 }
 
+impl Model {
+    /// Typed capabilities derived from the descriptive tags and the model flags.
+    pub fn typed_capabilities(&self) -> Vec<Capability> {
+        // Every DeepSeek model is served through the chat completions endpoint.
+        let mut capabilities = vec![Capability::Chat];
+        for capability in self
+            .capabilities
+            .iter()
+            .filter_map(|tag| Capability::from_tag(tag))
+        {
+            if !capabilities.contains(&capability) {
+                capabilities.push(capability);
+            }
+        }
+        if self.format == ApiFormat::OpenAI {
+            capabilities.push(Capability::JsonMode);
+        }
+        if self.supports_streaming {
+            capabilities.push(Capability::Streaming);
+        }
+        if self.supports_function_calling {
+            capabilities.push(Capability::FunctionCalling);
+        }
+        if self.context_length.unwrap_or(0) >= LONG_CONTEXT_TOKENS {
+            capabilities.push(Capability::LongContext);
+        }
+        capabilities.sort();
+        capabilities
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.typed_capabilities().contains(&capability)
+    }
+}
+
 pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
     let mut models = HashMap::new();
     // DeepSeek-Chat
@@ -83,6 +130,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("A general-purpose dialogue model with excellent performance, suitable for everyday conversations and question-and-answer sessions.".to_string()),
             max_tokens: Some(4096),
             context_length: Some(16384),
+            input_cost_per_1k_tokens: Some(0.00027),
+            output_cost_per_1k_tokens: Some(0.0011),
             capabilities: vec![
                 "chat".to_string(),
                 "text-generation".to_string(),
@@ -109,6 +158,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("Professional code generation model, supporting multiple programming languages.".to_string()),
             max_tokens: Some(8192),
             context_length: Some(32768),
+            input_cost_per_1k_tokens: Some(0.00027),
+            output_cost_per_1k_tokens: Some(0.0011),
             capabilities: vec![
                 "code-generation".to_string(),
                 "code-completion".to_string(),
@@ -135,6 +186,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("Deep reasoning models are suitable for complex logic analysis and problem solving.".to_string()),
             max_tokens: Some(8192),
             context_length: Some(32768),
+            input_cost_per_1k_tokens: Some(0.00055),
+            output_cost_per_1k_tokens: Some(0.00219),
             capabilities: vec![
                 "complex-reasoning".to_string(),
                 "problem-solving".to_string(),
@@ -160,6 +213,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("A specialized mathematical model that supports complex mathematical calculations and proofs.".to_string()),
             max_tokens: Some(4096),
             context_length: Some(16384),
+            input_cost_per_1k_tokens: Some(0.00027),
+            output_cost_per_1k_tokens: Some(0.0011),
             capabilities: vec![
                 "mathematical-reasoning".to_string(),
                 "calculation".to_string(),
@@ -185,6 +240,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("Dedicated financial analysis models to support market analysis and forecasting.".to_string()),
             max_tokens: Some(8192),
             context_length: Some(32768),
+            input_cost_per_1k_tokens: Some(0.00055),
+            output_cost_per_1k_tokens: Some(0.00219),
             capabilities: vec![
                 "financial-analysis".to_string(),
                 "market-prediction".to_string(),
@@ -211,6 +268,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("A dedicated medical and health model that supports diagnostic assistance and health consultation.".to_string()),
             max_tokens: Some(8192),
             context_length: Some(32768),
+            input_cost_per_1k_tokens: Some(0.00055),
+            output_cost_per_1k_tokens: Some(0.00219),
             capabilities: vec![
                 "medical-consultation".to_string(),
                 "diagnostic-support".to_string(),
@@ -236,6 +295,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("Creative writing model, suitable for story writing, poetry and creative content.".to_string()),
             max_tokens: Some(16384),
             context_length: Some(65536),
+            input_cost_per_1k_tokens: Some(0.00027),
+            output_cost_per_1k_tokens: Some(0.0011),
             capabilities: vec![
                 "creative-writing".to_string(),
                 "story-generation".to_string(),
@@ -262,6 +323,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("Enterprise-grade model, highest performance, suitable for mission-critical applications".to_string()),
             max_tokens: Some(32768),
             context_length: Some(131072),
+            input_cost_per_1k_tokens: Some(0.0011),
+            output_cost_per_1k_tokens: Some(0.0044),
             capabilities: vec![
                 "enterprise-ai".to_string(),
                 "business-intelligence".to_string(),
@@ -288,6 +351,8 @@ pub fn get_deepseek_models() -> HashMap<DeepSeekModelType, Model> {
             description: Some("An all-around model that balances performance and cost, suitable for a variety of application scenarios.".to_string()),
             max_tokens: Some(16384),
             context_length: Some(65536),
+            input_cost_per_1k_tokens: Some(0.00055),
+            output_cost_per_1k_tokens: Some(0.00219),
             capabilities: vec![
                 "multipurpose".to_string(),
                 "general-ai".to_string(),
@@ -343,7 +408,7 @@ pub fn get_coding_models() -> Vec<Model> {
 }
 
 pub fn get_specialized_models() -> Vec<Model> {
-    let specialized_capabilities = vec![
+    let specialized_capabilities = [
         "financial-analysis".to_string(),
         "medical-consultation".to_string(),
         "mathematical-reasoning".to_string(),
@@ -361,4 +426,16 @@ pub fn get_long_context_models() -> Vec<Model> {
         .into_iter()
         .filter(|model| model.context_length.unwrap_or(0) >= 32768)
         .collect()
-}
\ No newline at end of file
+}
+
+/// DeepSeek models supporting every capability, cheapest first; see
+/// [`find_models`].
+pub fn get_models_with_capabilities(capabilities: &[Capability]) -> Vec<Model> {
+    find_models(capabilities, None, None)
+        .into_iter()
+        .filter_map(|info| match info.id {
+            ModelId::DeepSeek(model_type) => get_model(model_type),
+            _ => None,
+        })
+        .collect()
+}
@@ -1,3 +1,5 @@
+use crate::types::ErrorKind;
+
 pub type DeepSeekStreamCallback = Box<dyn FnMut(String, bool) + Send>;
 
 #[derive(Debug, thiserror::Error)]
@@ -31,4 +33,21 @@ pub enum DeepSeekError {
     
     #[error("Context length exceeded: {0}")]
     ContextLengthError(String),
-}
\ No newline at end of file
+}
+
+impl DeepSeekError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ApiError(_) => ErrorKind::Api,
+            Self::NetworkError(_) => ErrorKind::Network,
+            Self::ParseError(_) => ErrorKind::Parse,
+            Self::ConfigError(_) => ErrorKind::Config,
+            Self::TimeoutError(_) => ErrorKind::Timeout,
+            Self::ModelNotSupported(_) => ErrorKind::ModelNotSupported,
+            Self::StreamingNotSupported => ErrorKind::StreamingNotSupported,
+            Self::RateLimitError(_) => ErrorKind::RateLimit,
+            Self::AuthError(_) => ErrorKind::Auth,
+            Self::ContextLengthError(_) => ErrorKind::ContextLength,
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{language::LanguagePack, types::OHLCV};
+
+/// How prices are represented in the prompt sent by `predict_ohlcv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PriceEncoding {
+    /// Absolute prices and volumes, as given.
+    #[default]
+    Raw,
+    /// Prices as percent change from the last close; volume as a multiple of the average volume.
+    PercentChange,
+    /// Prices as standard deviations of close from the last close; volume as a multiple of the average volume.
+    ZScore,
+}
+
+/// Encodes a series relative to its own statistics and maps model output back to absolute values.
+#[derive(Debug, Clone)]
+pub struct EncodingContext {
+    pub encoding: PriceEncoding,
+    pub reference_price: f64,
+    pub price_scale: f64,
+    pub volume_scale: f64,
+}
+
+impl EncodingContext {
+    /// Derives the reference values from `ohlcv_array`; falls back to `Raw`
+    /// when the series cannot be normalized (empty, zero last close or flat prices).
+    pub fn new(encoding: PriceEncoding, ohlcv_array: &[OHLCV]) -> Self {
+        let raw = Self {
+            encoding: PriceEncoding::Raw,
+            reference_price: 0.0,
+            price_scale: 1.0,
+            volume_scale: 1.0,
+        };
+        let Some(last) = ohlcv_array.last() else {
+            return raw;
+        };
+        let reference_price = last.close;
+        let price_scale = match encoding {
+            PriceEncoding::Raw => return raw,
+            PriceEncoding::PercentChange => reference_price / 100.0,
+            PriceEncoding::ZScore => {
+                let n = ohlcv_array.len() as f64;
+                let mean = ohlcv_array.iter().map(|bar| bar.close).sum::<f64>() / n;
+                (ohlcv_array
+                    .iter()
+                    .map(|bar| (bar.close - mean).powi(2))
+                    .sum::<f64>()
+                    / n)
+                    .sqrt()
+            }
+        };
+        if !price_scale.is_finite() || price_scale <= 0.0 {
+            return raw;
+        }
+        let average_volume =
+            ohlcv_array.iter().map(|bar| bar.volume).sum::<f64>() / ohlcv_array.len() as f64;
+        Self {
+            encoding,
+            reference_price,
+            price_scale,
+            volume_scale: if average_volume > 0.0 {
+                average_volume
+            } else {
+                1.0
+            },
+        }
+    }
+
+    pub fn is_raw(&self) -> bool {
+        self.encoding == PriceEncoding::Raw
+    }
+
+    pub fn encode(&self, ohlcv_array: &[OHLCV]) -> Vec<OHLCV> {
+        if self.is_raw() {
+            return ohlcv_array.to_vec();
+        }
+        let price = |p: f64| round4((p - self.reference_price) / self.price_scale);
+        ohlcv_array
+            .iter()
+            .map(|bar| OHLCV {
+                open: price(bar.open),
+                high: price(bar.high),
+                low: price(bar.low),
+                close: price(bar.close),
+                volume: round4(bar.volume / self.volume_scale),
+            })
+            .collect()
+    }
+
+    pub fn decode(&self, ohlcv_array: &[OHLCV]) -> Vec<OHLCV> {
+        if self.is_raw() {
+            return ohlcv_array.to_vec();
+        }
+        let price = |x: f64| self.reference_price + x * self.price_scale;
+        ohlcv_array
+            .iter()
+            .map(|bar| OHLCV {
+                open: price(bar.open),
+                high: price(bar.high),
+                low: price(bar.low),
+                close: price(bar.close),
+                volume: (bar.volume * self.volume_scale).max(0.0),
+            })
+            .collect()
+    }
+
+    /// Explains the encoding to the model in the language of `pack`; empty for `Raw`.
+    pub fn prompt_note<'a>(&self, pack: &'a LanguagePack) -> &'a str {
+        match self.encoding {
+            PriceEncoding::Raw => "",
+            PriceEncoding::PercentChange => &pack.percent_change_note,
+            PriceEncoding::ZScore => &pack.zscore_note,
+        }
+    }
+}
+
+/// Rounds to 4 decimals, enough precision for normalized values while keeping prompts short.
+pub(crate) fn round4(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars() -> Vec<OHLCV> {
+        [
+            (100.0, 102.0, 99.0, 101.0, 1000.0),
+            (101.0, 104.0, 100.5, 103.0, 3000.0),
+            (103.0, 103.5, 97.0, 98.0, 2000.0),
+            (98.0, 101.0, 97.5, 100.0, 2000.0),
+        ]
+        .iter()
+        .map(|&(open, high, low, close, volume)| OHLCV {
+            open,
+            high,
+            low,
+            close,
+            volume,
+        })
+        .collect()
+    }
+
+    fn assert_close(left: &[OHLCV], right: &[OHLCV], tolerance: f64) {
+        assert_eq!(left.len(), right.len());
+        for (a, b) in left.iter().zip(right) {
+            for (x, y) in [
+                (a.open, b.open),
+                (a.high, b.high),
+                (a.low, b.low),
+                (a.close, b.close),
+                (a.volume, b.volume),
+            ] {
+                assert!((x - y).abs() <= tolerance, "{} != {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn percent_change_is_relative_to_the_last_close() {
+        let context = EncodingContext::new(PriceEncoding::PercentChange, &bars());
+        let encoded = context.encode(&bars());
+        assert_eq!(encoded[3].close, 0.0);
+        assert_eq!(encoded[1].close, 3.0);
+        assert_eq!(encoded[0].volume, 0.5);
+        assert_close(&context.decode(&encoded), &bars(), 1e-9);
+    }
+
+    #[test]
+    fn zscore_round_trips_within_rounding() {
+        let context = EncodingContext::new(PriceEncoding::ZScore, &bars());
+        assert!(!context.is_raw());
+        let encoded = context.encode(&bars());
+        assert_eq!(encoded[3].close, 0.0);
+        assert_close(&context.decode(&encoded), &bars(), 1e-3);
+    }
+
+    #[test]
+    fn falls_back_to_raw_when_the_series_cannot_be_normalized() {
+        let mut flat = bars();
+        for bar in &mut flat {
+            bar.close = 100.0;
+        }
+        assert!(EncodingContext::new(PriceEncoding::ZScore, &flat).is_raw());
+        assert!(EncodingContext::new(PriceEncoding::PercentChange, &[]).is_raw());
+        let raw = EncodingContext::new(PriceEncoding::Raw, &bars());
+        assert_close(&raw.encode(&bars()), &bars(), 0.0);
+        assert_eq!(raw.prompt_note(&LanguagePack::english()), "");
+    }
+
+    #[test]
+    fn decoded_volume_is_never_negative() {
+        let context = EncodingContext::new(PriceEncoding::PercentChange, &bars());
+        let decoded = context.decode(&[OHLCV {
+            open: 0.0,
+            high: 1.0,
+            low: -1.0,
+            close: 0.5,
+            volume: -0.2,
+        }]);
+        assert_eq!(decoded[0].close, 100.5);
+        assert_eq!(decoded[0].volume, 0.0);
+    }
+}
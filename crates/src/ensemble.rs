@@ -0,0 +1,264 @@
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    aliyun::{AliYunModelType, AliyunAI},
+    deepseek::{DeepSeekAI, DeepSeekModelType},
+    router::{ProviderClient, RouteFailure, RouteOptions},
+    types::{ModelId, OHLCV},
+};
+
+/// How the per-model values of a field are combined into the consensus value.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Aggregation {
+    Median,
+    Mean,
+    /// Mean after discarding the given fraction (0.0..0.5) of values at each end.
+    TrimmedMean(f64),
+}
+
+#[derive(Clone)]
+pub struct EnsembleMember {
+    pub client: ProviderClient,
+    pub model: ModelId,
+}
+
+impl EnsembleMember {
+    pub fn aliyun(client: Arc<AliyunAI>, model_type: AliYunModelType) -> Self {
+        Self {
+            client: ProviderClient::Aliyun(client),
+            model: ModelId::Aliyun(model_type),
+        }
+    }
+
+    pub fn deepseek(client: Arc<DeepSeekAI>, model_type: DeepSeekModelType) -> Self {
+        Self {
+            client: ProviderClient::DeepSeek(client),
+            model: ModelId::DeepSeek(model_type),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnsembleOptions {
+    pub aggregation: Aggregation,
+    /// Minimum number of members that must return a valid forecast.
+    pub min_successful: usize,
+    pub request: RouteOptions,
+}
+
+impl Default for EnsembleOptions {
+    fn default() -> Self {
+        Self {
+            aggregation: Aggregation::Median,
+            min_successful: 2,
+            request: RouteOptions::default(),
+        }
+    }
+}
+
+/// A single member's validated forecast and how far it strays from the consensus.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelContribution {
+    pub model: ModelId,
+    pub forecast: Vec<OHLCV>,
+    /// Mean absolute deviation of the member's close from the consensus close, relative to it.
+    pub close_deviation: f64,
+}
+
+/// Standard deviation of each field across members for one forecast period.
+#[derive(Debug, Clone, Serialize)]
+pub struct Dispersion {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnsembleForecast {
+    pub consensus: Vec<OHLCV>,
+    pub contributions: Vec<ModelContribution>,
+    /// One entry per forecast period.
+    pub dispersion: Vec<Dispersion>,
+    pub failures: Vec<RouteFailure>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnsembleError {
+    #[error("Invalid configuration: {0}")]
+    ConfigError(String),
+    #[error("Only {succeeded} of the required {required} models returned a valid forecast")]
+    InsufficientForecasts {
+        required: usize,
+        succeeded: usize,
+        failures: Vec<RouteFailure>,
+    },
+}
+
+/// Fans one OHLCV history out to several models and merges their forecasts.
+#[derive(Clone, Default)]
+pub struct Ensemble {
+    members: Vec<EnsembleMember>,
+}
+
+impl Ensemble {
+    pub fn new(members: Vec<EnsembleMember>) -> Self {
+        Self { members }
+    }
+
+    pub fn add_member(&mut self, member: EnsembleMember) {
+        self.members.push(member);
+    }
+
+    pub async fn predict_ohlcv(
+        &self,
+        ohlcv_array: &[OHLCV],
+        instructions: Option<&str>,
+        count: Option<usize>,
+        options: Option<EnsembleOptions>,
+    ) -> Result<EnsembleForecast, EnsembleError> {
+        let opts = options.unwrap_or_default();
+        if self.members.is_empty() {
+            return Err(EnsembleError::ConfigError(
+                "Ensemble has no members".to_string(),
+            ));
+        }
+        if let Aggregation::TrimmedMean(fraction) = opts.aggregation
+            && !(0.0..0.5).contains(&fraction)
+        {
+            return Err(EnsembleError::ConfigError(format!(
+                "Trim fraction must be in [0, 0.5), got {}",
+                fraction
+            )));
+        }
+        let results = join_all(self.members.iter().map(|member| {
+            member.client.predict_ohlcv(
+                member.model,
+                ohlcv_array,
+                instructions,
+                count,
+                opts.request.clone(),
+            )
+        }))
+        .await;
+        let mut forecasts = Vec::new();
+        let mut failures = Vec::new();
+        for (member, result) in self.members.iter().zip(results) {
+            match result {
+                Ok(forecast) => forecasts.push((member.model, forecast)),
+                Err((kind, message)) => failures.push(RouteFailure {
+                    target: member.model,
+                    kind,
+                    message,
+                }),
+            }
+        }
+        let required = opts.min_successful.max(1);
+        if forecasts.len() < required {
+            return Err(EnsembleError::InsufficientForecasts {
+                required,
+                succeeded: forecasts.len(),
+                failures,
+            });
+        }
+        let periods = forecasts[0].1.len();
+        let mut consensus = Vec::with_capacity(periods);
+        let mut dispersion = Vec::with_capacity(periods);
+        for period in 0..periods {
+            let bars: Vec<&OHLCV> = forecasts.iter().map(|(_, f)| &f[period]).collect();
+            let field =
+                |get: fn(&OHLCV) -> f64| -> Vec<f64> { bars.iter().map(|b| get(b)).collect() };
+            let (opens, highs, lows, closes, volumes) = (
+                field(|b| b.open),
+                field(|b| b.high),
+                field(|b| b.low),
+                field(|b| b.close),
+                field(|b| b.volume),
+            );
+            let open = aggregate(&opens, opts.aggregation);
+            let close = aggregate(&closes, opts.aggregation);
+            // Independently aggregated fields can violate the bar invariants, so widen the range.
+            consensus.push(OHLCV {
+                open,
+                high: aggregate(&highs, opts.aggregation).max(open).max(close),
+                low: aggregate(&lows, opts.aggregation).min(open).min(close),
+                close,
+                volume: aggregate(&volumes, opts.aggregation).max(0.0),
+            });
+            dispersion.push(Dispersion {
+                open: std_dev(&opens),
+                high: std_dev(&highs),
+                low: std_dev(&lows),
+                close: std_dev(&closes),
+                volume: std_dev(&volumes),
+            });
+        }
+        let contributions = forecasts
+            .into_iter()
+            .map(|(model, forecast)| {
+                let close_deviation = forecast
+                    .iter()
+                    .zip(&consensus)
+                    .map(|(bar, agreed)| {
+                        if agreed.close != 0.0 {
+                            ((bar.close - agreed.close) / agreed.close).abs()
+                        } else {
+                            0.0
+                        }
+                    })
+                    .sum::<f64>()
+                    / periods.max(1) as f64;
+                ModelContribution {
+                    model,
+                    forecast,
+                    close_deviation,
+                }
+            })
+            .collect();
+        Ok(EnsembleForecast {
+            consensus,
+            contributions,
+            dispersion,
+            failures,
+        })
+    }
+}
+
+/// Combines values with the given aggregation; returns 0.0 for an empty slice.
+pub fn aggregate(values: &[f64], aggregation: Aggregation) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    match aggregation {
+        Aggregation::Median => {
+            let mid = sorted.len() / 2;
+            if sorted.len().is_multiple_of(2) {
+                (sorted[mid - 1] + sorted[mid]) / 2.0
+            } else {
+                sorted[mid]
+            }
+        }
+        Aggregation::Mean => mean(&sorted),
+        Aggregation::TrimmedMean(fraction) => {
+            let trim = (sorted.len() as f64 * fraction).floor() as usize;
+            mean(&sorted[trim..sorted.len() - trim])
+        }
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let avg = mean(values);
+    (values.iter().map(|v| (v - avg).powi(2)).sum::<f64>() / values.len() as f64).sqrt()
+}
@@ -0,0 +1,127 @@
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    capability::Capability,
+    serialization::strip_code_fences,
+    types::{ChatMessage, ChatRole, ResponseFormat},
+};
+
+/// JSON schema of `T`, as embedded in `chat_extract` prompts.
+pub fn extraction_schema<T: JsonSchema>() -> Value {
+    schemars::schema_for!(T).to_value()
+}
+
+/// Strictest `response_format` a model with `capabilities` accepts for `T`. JSON mode
+/// only produces objects, so it is not used for other schemas.
+pub(crate) fn extraction_format<T: JsonSchema>(
+    schema: &Value,
+    capabilities: &[Capability],
+) -> Option<ResponseFormat> {
+    if capabilities.contains(&Capability::JsonSchema) {
+        // Schema names may only contain letters, digits, '_' and '-'.
+        let name = T::schema_name()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Some(ResponseFormat::JsonSchema {
+            name,
+            schema: schema.clone(),
+            strict: false,
+        })
+    } else if capabilities.contains(&Capability::JsonMode)
+        && schema.get("type").and_then(|t| t.as_str()) == Some("object")
+    {
+        Some(ResponseFormat::JsonObject)
+    } else {
+        None
+    }
+}
+
+/// Adds the schema instruction to the system message, or prepends one when there is none.
+pub(crate) fn extraction_messages(messages: &[ChatMessage], schema: &Value) -> Vec<ChatMessage> {
+    let instruction = format!(
+        "Respond with a single JSON value that conforms to this JSON schema, without markdown or any other text:\n{}",
+        schema
+    );
+    let mut messages = messages.to_vec();
+    match messages
+        .iter_mut()
+        .find(|m| matches!(m.role, ChatRole::System))
+    {
+        Some(message) => message.content = format!("{}\n\n{}", message.content, instruction),
+        None => messages.insert(
+            0,
+            ChatMessage {
+                role: ChatRole::System,
+                content: instruction,
+            },
+        ),
+    }
+    messages
+}
+
+/// Deserializes the first JSON value in a reply into `T`. Code fences and text
+/// around the value are ignored; only the value itself is parsed, so trailing
+/// brackets in the prose do not break it.
+pub fn parse_extraction<T: DeserializeOwned>(content: &str) -> Result<T, String> {
+    let content = strip_code_fences(content);
+    let value = match serde_json::from_str::<Value>(content.trim()) {
+        Ok(value) => value,
+        Err(e) => {
+            let start = content
+                .find(['{', '['])
+                .ok_or_else(|| format!("No JSON value found in response: {}", e))?;
+            serde_json::Deserializer::from_str(&content[start..])
+                .into_iter::<Value>()
+                .next()
+                .ok_or_else(|| "No JSON value found in response".to_string())?
+                .map_err(|e| format!("Failed to parse JSON: {}", e))?
+        }
+    };
+    serde_json::from_value(value).map_err(|e| format!("Reply does not match the schema: {}", e))
+}
+
+/// The first JSON object in a reply. Each `{` is tried in turn and exactly one value
+/// is parsed from it, so braces in the surrounding prose are skipped.
+pub(crate) fn find_json_object(content: &str) -> Result<Value, String> {
+    let content = strip_code_fences(content);
+    let mut error = None;
+    for (at, _) in content.match_indices('{') {
+        match serde_json::Deserializer::from_str(&content[at..])
+            .into_iter::<Value>()
+            .next()
+        {
+            Some(Ok(value)) => return Ok(value),
+            Some(Err(e)) => {
+                error.get_or_insert_with(|| format!("Failed to parse JSON: {}", e));
+            }
+            None => {}
+        }
+    }
+    Err(error.unwrap_or_else(|| "No JSON object found in response".to_string()))
+}
+
+/// The rejected reply and a request to fix it, appended to the conversation before retrying.
+pub(crate) fn retry_messages(content: &str, error: &str) -> [ChatMessage; 2] {
+    [
+        ChatMessage {
+            role: ChatRole::Assistant,
+            content: content.to_string(),
+        },
+        ChatMessage {
+            role: ChatRole::User,
+            content: format!(
+                "Your previous reply could not be used: {}\nReply again with only the corrected JSON.",
+                error
+            ),
+        },
+    ]
+}
@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    io::{TimestampFormat, format_timestamp},
+    language::{LanguagePack, fill},
+    types::Candle,
+};
+
+/// A run of missing bars between two consecutive candles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Gap {
+    /// Index of the candle preceding the gap in the input series.
+    pub after_index: usize,
+    /// Timestamp of the first missing bar.
+    pub start: i64,
+    /// Timestamp of the last missing bar.
+    pub end: i64,
+    pub missing: usize,
+}
+
+impl Gap {
+    pub fn describe(&self, pack: &LanguagePack) -> String {
+        fill(
+            &pack.gap,
+            &[
+                ("missing", &self.missing.to_string()),
+                (
+                    "start",
+                    &format_timestamp(self.start, TimestampFormat::Rfc3339),
+                ),
+                ("end", &format_timestamp(self.end, TimestampFormat::Rfc3339)),
+            ],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FillStrategy {
+    /// Insert flat bars at the previous close with zero volume.
+    ForwardFill,
+    /// Insert bars walking linearly from the previous close to the next open.
+    Interpolate,
+    /// Keep the series as is and only report the gaps.
+    Leave,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GapFillResult {
+    pub candles: Vec<Candle>,
+    pub gaps: Vec<Gap>,
+    /// Number of bars inserted.
+    pub filled: usize,
+}
+
+impl GapFillResult {
+    /// Describes the gaps for a prompt, saying whether they were filled; empty when
+    /// there are none.
+    pub fn prompt_note(&self, pack: &LanguagePack) -> String {
+        let heading = if self.filled > 0 {
+            &pack.filled_gaps_heading
+        } else {
+            &pack.gaps_heading
+        };
+        gaps_note(heading, &self.gaps, pack)
+    }
+}
+
+/// Finds missing bars against `step_millis`, or the series step when `None`.
+/// Candles are expected in ascending timestamp order.
+pub fn detect_gaps(candles: &[Candle], step_millis: Option<i64>) -> Vec<Gap> {
+    let Some(step) = step_millis.or_else(|| Candle::series_step_millis(candles)) else {
+        return Vec::new();
+    };
+    if step <= 0 {
+        return Vec::new();
+    }
+    candles
+        .windows(2)
+        .enumerate()
+        .filter_map(|(index, pair)| {
+            let missing = ((pair[1].timestamp - pair[0].timestamp) / step - 1).max(0) as usize;
+            (missing > 0).then(|| Gap {
+                after_index: index,
+                start: pair[0].timestamp + step,
+                end: pair[0].timestamp + step * missing as i64,
+                missing,
+            })
+        })
+        .collect()
+}
+
+pub fn fill_gaps(
+    candles: &[Candle],
+    strategy: FillStrategy,
+    step_millis: Option<i64>,
+) -> GapFillResult {
+    let gaps = detect_gaps(candles, step_millis);
+    if strategy == FillStrategy::Leave || gaps.is_empty() {
+        return GapFillResult {
+            candles: candles.to_vec(),
+            gaps,
+            filled: 0,
+        };
+    }
+    let step = step_millis
+        .or_else(|| Candle::series_step_millis(candles))
+        .unwrap_or(0);
+    let mut result =
+        Vec::with_capacity(candles.len() + gaps.iter().map(|g| g.missing).sum::<usize>());
+    let mut filled = 0;
+    let mut gap_iter = gaps.iter().peekable();
+    for (index, candle) in candles.iter().enumerate() {
+        result.push(candle.clone());
+        let Some(gap) = gap_iter.next_if(|gap| gap.after_index == index) else {
+            continue;
+        };
+        let next = &candles[index + 1];
+        let mut previous_close = candle.close;
+        for k in 1..=gap.missing {
+            let timestamp = candle.timestamp + step * k as i64;
+            let bar = match strategy {
+                FillStrategy::ForwardFill => Candle {
+                    timestamp,
+                    open: candle.close,
+                    high: candle.close,
+                    low: candle.close,
+                    close: candle.close,
+                    volume: 0.0,
+                    ..candle.clone()
+                },
+                _ => {
+                    let t = k as f64 / (gap.missing + 1) as f64;
+                    let close = candle.close + (next.open - candle.close) * t;
+                    Candle {
+                        timestamp,
+                        open: previous_close,
+                        high: previous_close.max(close),
+                        low: previous_close.min(close),
+                        close,
+                        volume: candle.volume + (next.volume - candle.volume) * t,
+                        ..candle.clone()
+                    }
+                }
+            };
+            previous_close = bar.close;
+            result.push(bar);
+            filled += 1;
+        }
+    }
+    GapFillResult {
+        candles: result,
+        gaps,
+        filled,
+    }
+}
+
+/// Summarizes gaps as a note that can be appended to a prompt; empty when there are none.
+pub fn describe_gaps(gaps: &[Gap], pack: &LanguagePack) -> String {
+    gaps_note(&pack.gaps_heading, gaps, pack)
+}
+
+fn gaps_note(heading: &str, gaps: &[Gap], pack: &LanguagePack) -> String {
+    if gaps.is_empty() {
+        return String::new();
+    }
+    let total: usize = gaps.iter().map(|gap| gap.missing).sum();
+    let details: Vec<String> = gaps
+        .iter()
+        .map(|gap| format!("- {}", gap.describe(pack)))
+        .collect();
+    format!(
+        "{}\n{}",
+        fill(
+            heading,
+            &[
+                ("missing", &total.to_string()),
+                ("gaps", &gaps.len().to_string())
+            ]
+        ),
+        details.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000;
+
+    /// One-minute bars with two bars missing after index 1.
+    fn candles() -> Vec<Candle> {
+        [
+            (0, 10.0, 11.0, 9.0, 10.5, 100.0),
+            (1, 10.5, 11.5, 10.0, 11.0, 200.0),
+            (4, 12.0, 12.5, 11.5, 12.2, 500.0),
+            (5, 12.2, 12.8, 12.0, 12.6, 300.0),
+        ]
+        .iter()
+        .map(|&(minute, open, high, low, close, volume)| Candle {
+            timestamp: minute * MINUTE,
+            open,
+            high,
+            low,
+            close,
+            volume,
+            symbol: None,
+            interval: None,
+        })
+        .collect()
+    }
+
+    fn timestamps(candles: &[Candle]) -> Vec<i64> {
+        candles.iter().map(|c| c.timestamp / MINUTE).collect()
+    }
+
+    #[test]
+    fn detects_missing_bars_against_the_series_step() {
+        let gaps = detect_gaps(&candles(), None);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].after_index, 1);
+        assert_eq!(gaps[0].missing, 2);
+        assert_eq!((gaps[0].start, gaps[0].end), (2 * MINUTE, 3 * MINUTE));
+        assert!(detect_gaps(&candles(), Some(4 * MINUTE)).is_empty());
+    }
+
+    #[test]
+    fn forward_fill_repeats_the_previous_close_without_volume() {
+        let result = fill_gaps(&candles(), FillStrategy::ForwardFill, None);
+        assert_eq!(result.filled, 2);
+        assert_eq!(timestamps(&result.candles), vec![0, 1, 2, 3, 4, 5]);
+        for bar in &result.candles[2..4] {
+            assert_eq!(
+                (bar.open, bar.high, bar.low, bar.close),
+                (11.0, 11.0, 11.0, 11.0)
+            );
+            assert_eq!(bar.volume, 0.0);
+        }
+    }
+
+    #[test]
+    fn interpolate_walks_from_the_previous_close_to_the_next_open() {
+        let result = fill_gaps(&candles(), FillStrategy::Interpolate, None);
+        assert_eq!(result.filled, 2);
+        let filled = &result.candles[2..4];
+        assert!((filled[0].close - 11.333333).abs() < 1e-6);
+        assert!((filled[1].close - 11.666667).abs() < 1e-6);
+        assert_eq!(filled[0].open, 11.0);
+        assert_eq!(filled[1].open, filled[0].close);
+        assert!((filled[0].volume - 300.0).abs() < 1e-9);
+        assert!(
+            filled
+                .iter()
+                .all(|bar| bar.low <= bar.open && bar.high >= bar.close)
+        );
+    }
+
+    #[test]
+    fn leave_only_reports_the_gaps() {
+        let result = fill_gaps(&candles(), FillStrategy::Leave, None);
+        assert_eq!(result.filled, 0);
+        assert_eq!(timestamps(&result.candles), vec![0, 1, 4, 5]);
+        assert_eq!(result.gaps.len(), 1);
+    }
+
+    #[test]
+    fn prompt_note_says_whether_the_gaps_were_filled() {
+        let pack = LanguagePack::english();
+        let left = fill_gaps(&candles(), FillStrategy::Leave, None).prompt_note(&pack);
+        let filled = fill_gaps(&candles(), FillStrategy::ForwardFill, None).prompt_note(&pack);
+        assert!(left.starts_with("Note: the series has 2 missing period(s) in 1 gap(s):"));
+        assert!(filled.contains("were filled with synthetic bars"));
+        assert!(filled.contains("- 2 missing bar(s) from 1970-01-01T00:02:00"));
+        assert_eq!(left, describe_gaps(&detect_gaps(&candles(), None), &pack));
+        assert!(describe_gaps(&[], &pack).is_empty());
+    }
+}
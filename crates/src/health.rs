@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::types::{ErrorKind, ModelId, Provider};
+
+/// Prompt used for the plain chat round-trip probe.
+pub const HEALTH_CHAT_PROMPT: &str = "Hello, respond with \"OK\" if you can hear me.";
+
+/// Prompt used to verify the model can answer with a bare JSON object.
+pub const HEALTH_JSON_PROMPT: &str =
+    "Respond with exactly this JSON object and nothing else: {\"status\": \"ok\"}";
+
+/// Models probed at once by `health_check_all`.
+pub const HEALTH_CHECK_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthStatus {
+    Valid,
+    Invalid,
+    /// The probe failed before the credentials could be checked.
+    Unknown,
+}
+
+impl AuthStatus {
+    /// Auth status implied by how a request ended: `None` for success, or the kind
+    /// of error it failed with.
+    pub fn from_error_kind(error: Option<ErrorKind>) -> Self {
+        match error {
+            None => Self::Valid,
+            Some(ErrorKind::Auth) => Self::Invalid,
+            // Any response from the API other than 401 means the key was accepted.
+            Some(ErrorKind::RateLimit | ErrorKind::ContextLength | ErrorKind::Parse) => Self::Valid,
+            Some(_) => Self::Unknown,
+        }
+    }
+}
+
+/// Outcome of probing a single provider/model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub provider: Provider,
+    pub model: ModelId,
+    /// Whether the chat probe succeeded. The streaming and JSON mode probes only run
+    /// after it and are reported in their own fields; their failures leave this `true`.
+    pub healthy: bool,
+    pub auth: AuthStatus,
+    /// Round-trip time of the chat probe.
+    pub latency: Option<Duration>,
+    pub streaming_verified: bool,
+    pub json_mode_verified: bool,
+    pub error_kind: Option<ErrorKind>,
+    pub error: Option<String>,
+}
+
+impl HealthReport {
+    pub(crate) fn failed(model: ModelId, kind: ErrorKind, error: String) -> Self {
+        Self {
+            provider: model.provider(),
+            model,
+            healthy: false,
+            auth: AuthStatus::from_error_kind(Some(kind)),
+            latency: None,
+            streaming_verified: false,
+            json_mode_verified: false,
+            error_kind: Some(kind),
+            error: Some(error),
+        }
+    }
+}
+
+/// Whether a reply to `HEALTH_JSON_PROMPT` is a JSON object, tolerating surrounding whitespace.
+pub fn is_json_object_reply(content: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(content.trim())
+        .map(|value| value.is_object())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_rejected_key_is_invalid() {
+        assert_eq!(AuthStatus::from_error_kind(None), AuthStatus::Valid);
+        assert_eq!(
+            AuthStatus::from_error_kind(Some(ErrorKind::Auth)),
+            AuthStatus::Invalid
+        );
+        assert_eq!(
+            AuthStatus::from_error_kind(Some(ErrorKind::RateLimit)),
+            AuthStatus::Valid
+        );
+        assert_eq!(
+            AuthStatus::from_error_kind(Some(ErrorKind::Network)),
+            AuthStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn json_replies_must_be_bare_objects() {
+        assert!(is_json_object_reply(" {\"status\": \"ok\"}\n"));
+        assert!(!is_json_object_reply("```json\n{\"status\": \"ok\"}\n```"));
+        assert!(!is_json_object_reply("[1, 2]"));
+    }
+}
@@ -0,0 +1,750 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::{
+    encoding::round4,
+    language::{LanguagePack, fill},
+    summary::OhlcvSummary,
+    types::OHLCV,
+};
+
+/// A streaming indicator fed one bar at a time. `update` returns `None` while
+/// the indicator is still warming up.
+///
+/// The batch functions in this module run these same states over a slice, so
+/// live updates and historical series always agree.
+pub trait Indicator {
+    type Output;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<Self::Output>;
+
+    /// Forgets all bars seen so far.
+    fn reset(&mut self);
+}
+
+/// Runs `indicator` over `ohlcv_array`, returning one value per bar.
+pub fn run<I: Indicator>(mut indicator: I, ohlcv_array: &[OHLCV]) -> Vec<Option<I::Output>> {
+    ohlcv_array
+        .iter()
+        .map(|bar| indicator.update(bar))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MacdValue {
+    pub macd: f64,
+    pub signal: f64,
+    pub histogram: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BollingerValue {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+/// Simple moving average of close. Periods below 1 are treated as 1.
+#[derive(Debug, Clone)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            window: VecDeque::new(),
+            sum: 0.0,
+        }
+    }
+
+    fn push(&mut self, value: f64) -> Option<f64> {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap_or(0.0);
+        }
+        (self.window.len() == self.period).then(|| self.sum / self.period as f64)
+    }
+}
+
+impl Indicator for Sma {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<f64> {
+        self.push(bar.close)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+/// Exponential moving average of close, seeded with the SMA of the first `period` closes.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    seed: Sma,
+    value: Option<f64>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            seed: Sma::new(period),
+            value: None,
+        }
+    }
+
+    fn push(&mut self, value: f64) -> Option<f64> {
+        self.value = match self.value {
+            Some(previous) => {
+                let alpha = 2.0 / (self.period as f64 + 1.0);
+                Some(previous + alpha * (value - previous))
+            }
+            None => self.seed.push(value),
+        };
+        self.value
+    }
+}
+
+impl Indicator for Ema {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<f64> {
+        self.push(bar.close)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+/// Wilder's relative strength index of close, in [0, 100].
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    period: usize,
+    previous_close: Option<f64>,
+    changes: usize,
+    average_gain: f64,
+    average_loss: f64,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            previous_close: None,
+            changes: 0,
+            average_gain: 0.0,
+            average_loss: 0.0,
+        }
+    }
+}
+
+impl Indicator for Rsi {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<f64> {
+        let previous = self.previous_close.replace(bar.close)?;
+        let change = bar.close - previous;
+        let (gain, loss) = (change.max(0.0), (-change).max(0.0));
+        let period = self.period as f64;
+        self.changes += 1;
+        if self.changes <= self.period {
+            // Simple average over the first `period` changes.
+            self.average_gain += gain / period;
+            self.average_loss += loss / period;
+            if self.changes < self.period {
+                return None;
+            }
+        } else {
+            self.average_gain = (self.average_gain * (period - 1.0) + gain) / period;
+            self.average_loss = (self.average_loss * (period - 1.0) + loss) / period;
+        }
+        Some(match (self.average_gain, self.average_loss) {
+            (0.0, 0.0) => 50.0,
+            (_, 0.0) => 100.0,
+            (gain, loss) => 100.0 - 100.0 / (1.0 + gain / loss),
+        })
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+/// MACD line, signal line and histogram; emits once the signal line is ready.
+#[derive(Debug, Clone)]
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+}
+
+impl Macd {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Self {
+        Self {
+            fast: Ema::new(fast),
+            slow: Ema::new(slow),
+            signal: Ema::new(signal),
+        }
+    }
+}
+
+impl Default for Macd {
+    fn default() -> Self {
+        Self::new(12, 26, 9)
+    }
+}
+
+impl Indicator for Macd {
+    type Output = MacdValue;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<MacdValue> {
+        let fast = self.fast.push(bar.close);
+        let macd = fast.zip(self.slow.push(bar.close)).map(|(f, s)| f - s)?;
+        let signal = self.signal.push(macd)?;
+        Some(MacdValue {
+            macd,
+            signal,
+            histogram: macd - signal,
+        })
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.fast.period, self.slow.period, self.signal.period);
+    }
+}
+
+/// Bollinger bands: SMA of close plus/minus `multiplier` population standard deviations.
+#[derive(Debug, Clone)]
+pub struct Bollinger {
+    sma: Sma,
+    multiplier: f64,
+}
+
+impl Bollinger {
+    pub fn new(period: usize, multiplier: f64) -> Self {
+        Self {
+            sma: Sma::new(period),
+            multiplier,
+        }
+    }
+}
+
+impl Default for Bollinger {
+    fn default() -> Self {
+        Self::new(20, 2.0)
+    }
+}
+
+impl Indicator for Bollinger {
+    type Output = BollingerValue;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<BollingerValue> {
+        let middle = self.sma.push(bar.close)?;
+        let variance = self
+            .sma
+            .window
+            .iter()
+            .map(|close| (close - middle).powi(2))
+            .sum::<f64>()
+            / self.sma.period as f64;
+        let band = self.multiplier * variance.sqrt();
+        Some(BollingerValue {
+            upper: middle + band,
+            middle,
+            lower: middle - band,
+        })
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.sma.period, self.multiplier);
+    }
+}
+
+/// Wilder's average true range.
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    previous_close: Option<f64>,
+    seed: Sma,
+    value: Option<f64>,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            previous_close: None,
+            seed: Sma::new(period),
+            value: None,
+        }
+    }
+}
+
+impl Indicator for Atr {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<f64> {
+        let range = bar.high - bar.low;
+        let true_range = match self.previous_close.replace(bar.close) {
+            Some(close) => range
+                .max((bar.high - close).abs())
+                .max((bar.low - close).abs()),
+            None => range,
+        };
+        let period = self.period as f64;
+        self.value = match self.value {
+            Some(previous) => Some((previous * (period - 1.0) + true_range) / period),
+            None => self.seed.push(true_range),
+        };
+        self.value
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+/// On-balance volume, starting at 0 on the first bar.
+#[derive(Debug, Clone, Default)]
+pub struct Obv {
+    previous_close: Option<f64>,
+    value: f64,
+}
+
+impl Obv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Indicator for Obv {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<f64> {
+        if let Some(previous) = self.previous_close {
+            if bar.close > previous {
+                self.value += bar.volume;
+            } else if bar.close < previous {
+                self.value -= bar.volume;
+            }
+        }
+        self.previous_close = Some(bar.close);
+        Some(self.value)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Cumulative volume-weighted average of the typical price `(high + low + close) / 3`.
+/// Call `reset` at each session start for a session-anchored VWAP.
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    price_volume: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Indicator for Vwap {
+    type Output = f64;
+
+    fn update(&mut self, bar: &OHLCV) -> Option<f64> {
+        self.price_volume += (bar.high + bar.low + bar.close) / 3.0 * bar.volume;
+        self.volume += bar.volume;
+        (self.volume > 0.0).then(|| self.price_volume / self.volume)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+pub fn sma(ohlcv_array: &[OHLCV], period: usize) -> Vec<Option<f64>> {
+    run(Sma::new(period), ohlcv_array)
+}
+
+pub fn ema(ohlcv_array: &[OHLCV], period: usize) -> Vec<Option<f64>> {
+    run(Ema::new(period), ohlcv_array)
+}
+
+pub fn rsi(ohlcv_array: &[OHLCV], period: usize) -> Vec<Option<f64>> {
+    run(Rsi::new(period), ohlcv_array)
+}
+
+pub fn macd(
+    ohlcv_array: &[OHLCV],
+    fast: usize,
+    slow: usize,
+    signal: usize,
+) -> Vec<Option<MacdValue>> {
+    run(Macd::new(fast, slow, signal), ohlcv_array)
+}
+
+pub fn bollinger(
+    ohlcv_array: &[OHLCV],
+    period: usize,
+    multiplier: f64,
+) -> Vec<Option<BollingerValue>> {
+    run(Bollinger::new(period, multiplier), ohlcv_array)
+}
+
+pub fn atr(ohlcv_array: &[OHLCV], period: usize) -> Vec<Option<f64>> {
+    run(Atr::new(period), ohlcv_array)
+}
+
+pub fn obv(ohlcv_array: &[OHLCV]) -> Vec<f64> {
+    run(Obv::new(), ohlcv_array).into_iter().flatten().collect()
+}
+
+pub fn vwap(ohlcv_array: &[OHLCV]) -> Vec<Option<f64>> {
+    run(Vwap::new(), ohlcv_array)
+}
+
+/// An indicator and its parameters, as selected for prompt context.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum IndicatorSpec {
+    Sma(usize),
+    Ema(usize),
+    Rsi(usize),
+    Macd {
+        fast: usize,
+        slow: usize,
+        signal: usize,
+    },
+    Bollinger {
+        period: usize,
+        multiplier: f64,
+    },
+    Atr(usize),
+    Obv,
+    Vwap,
+}
+
+impl IndicatorSpec {
+    /// Labelled series computed over `ohlcv_array`; multi-line indicators yield one entry per line.
+    pub fn series(&self, ohlcv_array: &[OHLCV]) -> Vec<(String, Vec<Option<f64>>)> {
+        match *self {
+            Self::Sma(period) => vec![(format!("SMA({})", period), sma(ohlcv_array, period))],
+            Self::Ema(period) => vec![(format!("EMA({})", period), ema(ohlcv_array, period))],
+            Self::Rsi(period) => vec![(format!("RSI({})", period), rsi(ohlcv_array, period))],
+            Self::Macd { fast, slow, signal } => {
+                let values = macd(ohlcv_array, fast, slow, signal);
+                let name = format!("MACD({},{},{})", fast, slow, signal);
+                vec![
+                    (
+                        name.clone(),
+                        values.iter().map(|v| v.map(|v| v.macd)).collect(),
+                    ),
+                    (
+                        format!("{} signal", name),
+                        values.iter().map(|v| v.map(|v| v.signal)).collect(),
+                    ),
+                    (
+                        format!("{} histogram", name),
+                        values.iter().map(|v| v.map(|v| v.histogram)).collect(),
+                    ),
+                ]
+            }
+            Self::Bollinger { period, multiplier } => {
+                let values = bollinger(ohlcv_array, period, multiplier);
+                let name = format!("Bollinger({},{})", period, multiplier);
+                vec![
+                    (
+                        format!("{} upper", name),
+                        values.iter().map(|v| v.map(|v| v.upper)).collect(),
+                    ),
+                    (
+                        format!("{} middle", name),
+                        values.iter().map(|v| v.map(|v| v.middle)).collect(),
+                    ),
+                    (
+                        format!("{} lower", name),
+                        values.iter().map(|v| v.map(|v| v.lower)).collect(),
+                    ),
+                ]
+            }
+            Self::Atr(period) => vec![(format!("ATR({})", period), atr(ohlcv_array, period))],
+            Self::Obv => vec![(
+                "OBV".to_string(),
+                obv(ohlcv_array).into_iter().map(Some).collect(),
+            )],
+            Self::Vwap => vec![("VWAP".to_string(), vwap(ohlcv_array))],
+        }
+    }
+}
+
+/// Locally computed context attached to `predict_ohlcv` prompts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndicatorContext {
+    pub indicators: Vec<IndicatorSpec>,
+    /// Number of most recent values listed per indicator.
+    pub tail: usize,
+    /// Also include the `OhlcvSummary` of the series.
+    pub include_summary: bool,
+}
+
+impl Default for IndicatorContext {
+    fn default() -> Self {
+        Self {
+            indicators: vec![
+                IndicatorSpec::Rsi(14),
+                IndicatorSpec::Macd {
+                    fast: 12,
+                    slow: 26,
+                    signal: 9,
+                },
+                IndicatorSpec::Bollinger {
+                    period: 20,
+                    multiplier: 2.0,
+                },
+                IndicatorSpec::Atr(14),
+            ],
+            tail: 5,
+            include_summary: true,
+        }
+    }
+}
+
+impl IndicatorContext {
+    /// Renders the context as a prompt section; empty when there is nothing to report.
+    /// Indicators that are still warming up over `ohlcv_array` are skipped.
+    /// Pass the raw bars: values are labelled as absolute prices and volumes.
+    pub fn prompt_note(&self, ohlcv_array: &[OHLCV], pack: &LanguagePack) -> String {
+        let mut lines = Vec::new();
+        if self.include_summary {
+            let summary = OhlcvSummary::from(ohlcv_array).describe(pack);
+            lines.extend(summary.lines().map(|line| format!("- {}", line)));
+        }
+        for spec in &self.indicators {
+            for (label, values) in spec.series(ohlcv_array) {
+                let ready: Vec<f64> = values.into_iter().flatten().collect();
+                let Some(latest) = ready.last() else {
+                    continue;
+                };
+                let tail: Vec<String> = ready[ready.len().saturating_sub(self.tail)..]
+                    .iter()
+                    .map(|v| round4(*v).to_string())
+                    .collect();
+                let line = fill(
+                    &pack.indicator,
+                    &[
+                        ("label", &label),
+                        ("latest", &round4(*latest).to_string()),
+                        ("count", &tail.len().to_string()),
+                        ("tail", &tail.join(", ")),
+                    ],
+                );
+                lines.push(format!("- {}", line));
+            }
+        }
+        if lines.is_empty() {
+            return String::new();
+        }
+        format!("{}\n{}", pack.indicators_heading, lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars() -> Vec<OHLCV> {
+        [
+            (10.0, 10.5, 9.5, 10.0, 100.0),
+            (10.0, 11.5, 9.8, 11.0, 200.0),
+            (11.0, 12.4, 10.9, 12.0, 150.0),
+            (12.0, 12.2, 10.6, 11.0, 120.0),
+            (11.0, 13.5, 10.8, 13.0, 300.0),
+            (13.0, 14.6, 12.7, 14.0, 250.0),
+        ]
+        .iter()
+        .map(|&(open, high, low, close, volume)| OHLCV {
+            open,
+            high,
+            low,
+            close,
+            volume,
+        })
+        .collect()
+    }
+
+    fn closes(values: &[f64]) -> Vec<OHLCV> {
+        values
+            .iter()
+            .map(|&close| OHLCV {
+                open: close,
+                high: close,
+                low: close,
+                close,
+                volume: 1.0,
+            })
+            .collect()
+    }
+
+    fn assert_series(actual: &[Option<f64>], expected: &[Option<f64>]) {
+        assert_eq!(actual.len(), expected.len());
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            match (a, e) {
+                (Some(a), Some(e)) => assert!((a - e).abs() < 1e-4, "bar {}: {} != {}", i, a, e),
+                _ => assert_eq!(a.is_some(), e.is_some(), "bar {}: {:?} != {:?}", i, a, e),
+            }
+        }
+    }
+
+    /// Feeds bars one at a time and checks each value against the batch function
+    /// recomputed over the bars seen so far, then again after `reset`.
+    fn assert_streaming_matches_batch<I>(
+        mut indicator: I,
+        batch: impl Fn(&[OHLCV]) -> Vec<Option<I::Output>>,
+    ) where
+        I: Indicator,
+        I::Output: PartialEq + std::fmt::Debug,
+    {
+        let bars = bars();
+        for _ in 0..2 {
+            for i in 0..bars.len() {
+                let streamed = indicator.update(&bars[i]);
+                assert_eq!(streamed, batch(&bars[..=i]).pop().flatten(), "bar {}", i);
+            }
+            indicator.reset();
+        }
+    }
+
+    #[test]
+    fn sma_matches_reference() {
+        assert_series(
+            &sma(&bars(), 3),
+            &[
+                None,
+                None,
+                Some(11.0),
+                Some(11.3333),
+                Some(12.0),
+                Some(12.6667),
+            ],
+        );
+    }
+
+    #[test]
+    fn ema_is_seeded_with_sma() {
+        assert_series(
+            &ema(&bars(), 3),
+            &[None, None, Some(11.0), Some(11.0), Some(12.0), Some(13.0)],
+        );
+    }
+
+    #[test]
+    fn rsi_matches_wilder_example() {
+        let series = closes(&[
+            44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03,
+            45.61, 46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64,
+        ]);
+        let values = rsi(&series, 14);
+        assert!(values[..14].iter().all(Option::is_none));
+        let expected = [70.4641, 66.2496, 66.4809, 69.3469, 66.2947, 57.9150];
+        assert_series(&values[14..], &expected.map(Some));
+    }
+
+    #[test]
+    fn rsi_of_flat_and_rising_series() {
+        assert_eq!(rsi(&closes(&[5.0; 4]), 3)[3], Some(50.0));
+        assert_eq!(rsi(&closes(&[1.0, 2.0, 3.0, 4.0]), 3)[3], Some(100.0));
+    }
+
+    #[test]
+    fn macd_matches_reference() {
+        let values = macd(&bars(), 2, 3, 2);
+        assert!(values[..3].iter().all(Option::is_none));
+        let expected = [(0.1667, 0.3333), (0.3889, 0.3704), (0.4630, 0.4321)];
+        for (value, (macd, signal)) in values[3..].iter().zip(expected) {
+            let value = value.unwrap();
+            assert!((value.macd - macd).abs() < 1e-4);
+            assert!((value.signal - signal).abs() < 1e-4);
+            assert!((value.histogram - (value.macd - value.signal)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn bollinger_uses_population_deviation() {
+        let values = bollinger(&bars(), 3, 2.0);
+        assert!(values[..2].iter().all(Option::is_none));
+        let expected = [
+            (12.6330, 11.0, 9.3670),
+            (12.2761, 11.3333, 10.3905),
+            (13.6330, 12.0, 10.3670),
+            (15.1611, 12.6667, 10.1722),
+        ];
+        for (value, (upper, middle, lower)) in values[2..].iter().zip(expected) {
+            let value = value.unwrap();
+            assert!((value.upper - upper).abs() < 1e-4);
+            assert!((value.middle - middle).abs() < 1e-4);
+            assert!((value.lower - lower).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn atr_uses_true_range_and_wilder_smoothing() {
+        assert_series(
+            &atr(&bars(), 3),
+            &[
+                None,
+                None,
+                Some(1.4),
+                Some(1.4667),
+                Some(1.8778),
+                Some(1.8852),
+            ],
+        );
+    }
+
+    #[test]
+    fn obv_adds_up_and_down_volume() {
+        assert_eq!(obv(&bars()), vec![0.0, 200.0, 350.0, 230.0, 530.0, 780.0]);
+    }
+
+    #[test]
+    fn vwap_weights_typical_price_by_volume() {
+        assert_series(
+            &vwap(&bars()),
+            &[
+                Some(10.0),
+                Some(10.5111),
+                Some(10.9296),
+                Some(11.0006),
+                Some(11.4946),
+                Some(12.0018),
+            ],
+        );
+        let mut no_volume = closes(&[1.0]);
+        no_volume[0].volume = 0.0;
+        assert_eq!(vwap(&no_volume), vec![None]);
+    }
+
+    #[test]
+    fn streaming_matches_batch() {
+        assert_streaming_matches_batch(Sma::new(3), |bars| sma(bars, 3));
+        assert_streaming_matches_batch(Ema::new(3), |bars| ema(bars, 3));
+        assert_streaming_matches_batch(Rsi::new(3), |bars| rsi(bars, 3));
+        assert_streaming_matches_batch(Macd::new(2, 3, 2), |bars| macd(bars, 2, 3, 2));
+        assert_streaming_matches_batch(Bollinger::new(3, 2.0), |bars| bollinger(bars, 3, 2.0));
+        assert_streaming_matches_batch(Atr::new(3), |bars| atr(bars, 3));
+        assert_streaming_matches_batch(Obv::new(), |bars| {
+            obv(bars).into_iter().map(Some).collect()
+        });
+        assert_streaming_matches_batch(Vwap::new(), vwap);
+    }
+}
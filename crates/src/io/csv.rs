@@ -0,0 +1,546 @@
+use std::{fs::File, io, path::Path};
+
+use crate::{
+    io::{TimestampFormat, format_timestamp, parse_timestamp},
+    types::{Candle, OHLCV},
+};
+
+/// A column addressed by header name (case-insensitive) or zero-based position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Name(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnMapping {
+    pub timestamp: Option<Column>,
+    pub open: Column,
+    pub high: Column,
+    pub low: Column,
+    pub close: Column,
+    pub volume: Column,
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// `None` detects a header from the first row.
+    pub has_headers: Option<bool>,
+    /// `None` detects columns from header names, or by position without a header:
+    /// rows of six or more fields are read as `timestamp, open, high, low, close,
+    /// volume`, shorter ones as `open, high, low, close, volume`.
+    pub columns: Option<ColumnMapping>,
+    pub timestamp_format: TimestampFormat,
+    /// Collect invalid rows in `CsvImport::errors` instead of failing on the first one.
+    pub skip_invalid_rows: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_headers: None,
+            columns: None,
+            timestamp_format: TimestampFormat::Auto,
+            skip_invalid_rows: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvRowError {
+    /// One-based line number in the input.
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CsvError {
+    #[error("IO error: {0}")]
+    IoError(String),
+    #[error("Invalid header: {0}")]
+    HeaderError(String),
+    #[error("Line {}: {}", .0.line, .0.message)]
+    RowError(CsvRowError),
+}
+
+/// A parsed CSV row.
+#[derive(Debug, Clone)]
+pub struct CsvRow {
+    pub line: u64,
+    pub timestamp: Option<i64>,
+    pub ohlcv: OHLCV,
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvImport<T> {
+    pub rows: Vec<T>,
+    pub errors: Vec<CsvRowError>,
+}
+
+const TIMESTAMP_NAMES: [&str; 7] = [
+    "timestamp",
+    "time",
+    "date",
+    "datetime",
+    "open_time",
+    "opentime",
+    "t",
+];
+const OPEN_NAMES: [&str; 2] = ["open", "o"];
+const HIGH_NAMES: [&str; 2] = ["high", "h"];
+const LOW_NAMES: [&str; 2] = ["low", "l"];
+const CLOSE_NAMES: [&str; 2] = ["close", "c"];
+/// Only used when there is no plain close column.
+const ADJ_CLOSE_NAMES: [&str; 3] = ["adj_close", "adj close", "adjclose"];
+const VOLUME_NAMES: [&str; 3] = ["volume", "v", "vol"];
+
+struct ResolvedColumns {
+    timestamp: Option<usize>,
+    open: usize,
+    high: usize,
+    low: usize,
+    close: usize,
+    volume: usize,
+}
+
+/// Streaming CSV reader yielding one row at a time, so large files are never fully loaded.
+pub struct CsvReader<R: io::Read> {
+    records: csv::StringRecordsIntoIter<R>,
+    pending: Option<csv::StringRecord>,
+    columns: ResolvedColumns,
+    timestamp_format: TimestampFormat,
+}
+
+impl CsvReader<File> {
+    pub fn from_path<P: AsRef<Path>>(path: P, options: &CsvOptions) -> Result<Self, CsvError> {
+        let file = File::open(path).map_err(|e| CsvError::IoError(e.to_string()))?;
+        Self::new(file, options)
+    }
+}
+
+impl<R: io::Read> CsvReader<R> {
+    pub fn new(reader: R, options: &CsvOptions) -> Result<Self, CsvError> {
+        let mut records = csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(reader)
+            .into_records();
+        let first = records
+            .next()
+            .transpose()
+            .map_err(|e| CsvError::IoError(e.to_string()))?;
+        let has_headers = match (&first, options.has_headers) {
+            (_, Some(has_headers)) => has_headers,
+            (Some(record), None) => {
+                record.iter().any(|field| field.parse::<f64>().is_err())
+                    && parse_timestamp(record.get(0).unwrap_or(""), TimestampFormat::Auto).is_err()
+            }
+            (None, None) => false,
+        };
+        let (header, pending) = if has_headers {
+            (first, None)
+        } else {
+            (None, first)
+        };
+        let width = header
+            .as_ref()
+            .or(pending.as_ref())
+            .map(|record| record.len())
+            .unwrap_or(0);
+        let columns = match &options.columns {
+            Some(mapping) => resolve_mapping(mapping, header.as_ref())?,
+            None => detect_columns(header.as_ref(), width)?,
+        };
+        Ok(Self {
+            records,
+            pending,
+            columns,
+            timestamp_format: options.timestamp_format,
+        })
+    }
+
+    fn parse_record(&self, record: &csv::StringRecord) -> Result<CsvRow, CsvRowError> {
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+        let error = |message: String| CsvRowError { line, message };
+        let field = |index: usize, name: &str| -> Result<f64, CsvRowError> {
+            let raw = record
+                .get(index)
+                .ok_or_else(|| error(format!("missing '{}' column", name)))?;
+            raw.parse::<f64>()
+                .map_err(|_| error(format!("invalid '{}' value '{}'", name, raw)))
+        };
+        let timestamp = match self.columns.timestamp {
+            Some(index) => {
+                let raw = record
+                    .get(index)
+                    .ok_or_else(|| error("missing 'timestamp' column".to_string()))?;
+                Some(parse_timestamp(raw, self.timestamp_format).map_err(error)?)
+            }
+            None => None,
+        };
+        Ok(CsvRow {
+            line,
+            timestamp,
+            ohlcv: OHLCV {
+                open: field(self.columns.open, "open")?,
+                high: field(self.columns.high, "high")?,
+                low: field(self.columns.low, "low")?,
+                close: field(self.columns.close, "close")?,
+                volume: field(self.columns.volume, "volume")?,
+            },
+        })
+    }
+}
+
+impl<R: io::Read> Iterator for CsvReader<R> {
+    type Item = Result<CsvRow, CsvRowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.pending.take() {
+            Some(record) => record,
+            None => match self.records.next()? {
+                Ok(record) => record,
+                Err(e) => {
+                    return Some(Err(CsvRowError {
+                        line: e.position().map(|p| p.line()).unwrap_or(0),
+                        message: e.to_string(),
+                    }));
+                }
+            },
+        };
+        Some(self.parse_record(&record))
+    }
+}
+
+fn find_column(header: &csv::StringRecord, names: &[&str]) -> Option<usize> {
+    header
+        .iter()
+        .position(|field| names.iter().any(|name| field.eq_ignore_ascii_case(name)))
+}
+
+fn detect_columns(
+    header: Option<&csv::StringRecord>,
+    width: usize,
+) -> Result<ResolvedColumns, CsvError> {
+    match header {
+        Some(header) => {
+            let required = |names: &[&str]| {
+                find_column(header, names)
+                    .ok_or_else(|| CsvError::HeaderError(format!("no '{}' column found", names[0])))
+            };
+            Ok(ResolvedColumns {
+                timestamp: find_column(header, &TIMESTAMP_NAMES),
+                open: required(&OPEN_NAMES)?,
+                high: required(&HIGH_NAMES)?,
+                low: required(&LOW_NAMES)?,
+                close: required(&CLOSE_NAMES)
+                    .or_else(|e| find_column(header, &ADJ_CLOSE_NAMES).ok_or(e))?,
+                volume: required(&VOLUME_NAMES)?,
+            })
+        }
+        // Without a header assume `[timestamp,] open, high, low, close, volume`.
+        None if width >= 6 => Ok(ResolvedColumns {
+            timestamp: Some(0),
+            open: 1,
+            high: 2,
+            low: 3,
+            close: 4,
+            volume: 5,
+        }),
+        None => Ok(ResolvedColumns {
+            timestamp: None,
+            open: 0,
+            high: 1,
+            low: 2,
+            close: 3,
+            volume: 4,
+        }),
+    }
+}
+
+fn resolve_mapping(
+    mapping: &ColumnMapping,
+    header: Option<&csv::StringRecord>,
+) -> Result<ResolvedColumns, CsvError> {
+    let resolve = |column: &Column| match (column, header) {
+        (Column::Index(index), _) => Ok(*index),
+        (Column::Name(name), Some(header)) => find_column(header, &[name.as_str()])
+            .ok_or_else(|| CsvError::HeaderError(format!("no '{}' column found", name))),
+        (Column::Name(name), None) => Err(CsvError::HeaderError(format!(
+            "column '{}' is addressed by name but the input has no header",
+            name
+        ))),
+    };
+    Ok(ResolvedColumns {
+        timestamp: mapping.timestamp.as_ref().map(resolve).transpose()?,
+        open: resolve(&mapping.open)?,
+        high: resolve(&mapping.high)?,
+        low: resolve(&mapping.low)?,
+        close: resolve(&mapping.close)?,
+        volume: resolve(&mapping.volume)?,
+    })
+}
+
+fn collect<R: io::Read, T>(
+    reader: CsvReader<R>,
+    options: &CsvOptions,
+    convert: impl Fn(CsvRow) -> Result<T, CsvRowError>,
+) -> Result<CsvImport<T>, CsvError> {
+    let mut import = CsvImport {
+        rows: Vec::new(),
+        errors: Vec::new(),
+    };
+    for row in reader {
+        match row.and_then(&convert) {
+            Ok(value) => import.rows.push(value),
+            Err(e) if options.skip_invalid_rows => import.errors.push(e),
+            Err(e) => return Err(CsvError::RowError(e)),
+        }
+    }
+    Ok(import)
+}
+
+pub fn read_ohlcv_csv<R: io::Read>(
+    reader: R,
+    options: &CsvOptions,
+) -> Result<CsvImport<OHLCV>, CsvError> {
+    collect(CsvReader::new(reader, options)?, options, |row| {
+        Ok(row.ohlcv)
+    })
+}
+
+pub fn read_candles_csv<R: io::Read>(
+    reader: R,
+    options: &CsvOptions,
+) -> Result<CsvImport<Candle>, CsvError> {
+    let reader = CsvReader::new(reader, options)?;
+    if reader.columns.timestamp.is_none() {
+        return Err(CsvError::HeaderError(
+            "no 'timestamp' column found".to_string(),
+        ));
+    }
+    collect(reader, options, |row| {
+        let timestamp = row.timestamp.ok_or_else(|| CsvRowError {
+            line: row.line,
+            message: "no timestamp column".to_string(),
+        })?;
+        Ok(Candle::from_ohlcv(timestamp, &row.ohlcv))
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvWriteOptions {
+    pub delimiter: u8,
+    pub write_headers: bool,
+    pub timestamp_format: TimestampFormat,
+}
+
+impl Default for CsvWriteOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            write_headers: true,
+            timestamp_format: TimestampFormat::EpochMillis,
+        }
+    }
+}
+
+pub fn write_ohlcv_csv<W: io::Write>(
+    writer: W,
+    ohlcv_array: &[OHLCV],
+    options: &CsvWriteOptions,
+) -> Result<(), CsvError> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(writer);
+    let io_error = |e: csv::Error| CsvError::IoError(e.to_string());
+    if options.write_headers {
+        writer
+            .write_record(["open", "high", "low", "close", "volume"])
+            .map_err(io_error)?;
+    }
+    for bar in ohlcv_array {
+        writer
+            .write_record([
+                bar.open.to_string(),
+                bar.high.to_string(),
+                bar.low.to_string(),
+                bar.close.to_string(),
+                bar.volume.to_string(),
+            ])
+            .map_err(io_error)?;
+    }
+    writer.flush().map_err(|e| CsvError::IoError(e.to_string()))
+}
+
+pub fn write_candles_csv<W: io::Write>(
+    writer: W,
+    candles: &[Candle],
+    options: &CsvWriteOptions,
+) -> Result<(), CsvError> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(writer);
+    let io_error = |e: csv::Error| CsvError::IoError(e.to_string());
+    if options.write_headers {
+        writer
+            .write_record(["timestamp", "open", "high", "low", "close", "volume"])
+            .map_err(io_error)?;
+    }
+    for candle in candles {
+        writer
+            .write_record([
+                format_timestamp(candle.timestamp, options.timestamp_format),
+                candle.open.to_string(),
+                candle.high.to_string(),
+                candle.low.to_string(),
+                candle.close.to_string(),
+                candle.volume.to_string(),
+            ])
+            .map_err(io_error)?;
+    }
+    writer.flush().map_err(|e| CsvError::IoError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_candles(input: &str, options: &CsvOptions) -> CsvImport<Candle> {
+        read_candles_csv(input.as_bytes(), options).unwrap()
+    }
+
+    fn fields(candle: &Candle) -> (f64, f64, f64, f64, f64) {
+        (
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            candle.volume,
+        )
+    }
+
+    #[test]
+    fn detects_a_header_and_maps_columns_by_name() {
+        let input = "Date,Volume,Close,Low,High,Open\n2024-01-02,1000,10.5,9.5,11,10\n";
+        let import = read_candles(input, &CsvOptions::default());
+        assert!(import.errors.is_empty());
+        let candle = &import.rows[0];
+        assert_eq!(candle.timestamp, 1_704_153_600_000);
+        assert_eq!(
+            (
+                candle.open,
+                candle.high,
+                candle.low,
+                candle.close,
+                candle.volume
+            ),
+            (10.0, 11.0, 9.5, 10.5, 1000.0)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_adjusted_close() {
+        let input = "open,high,low,adj close,volume\n10,11,9,10.25,5\n";
+        let import = read_ohlcv_csv(input.as_bytes(), &CsvOptions::default()).unwrap();
+        assert_eq!(import.rows[0].close, 10.25);
+    }
+
+    #[test]
+    fn reads_headerless_rows_by_position() {
+        let five = read_ohlcv_csv("10,11,9,10.5,100\n".as_bytes(), &CsvOptions::default()).unwrap();
+        assert_eq!(five.rows[0].open, 10.0);
+        assert_eq!(five.rows[0].volume, 100.0);
+
+        // Six columns are taken as a leading timestamp, here in epoch seconds.
+        let six = read_candles("1704153600,10,11,9,10.5,100\n", &CsvOptions::default());
+        assert_eq!(six.rows[0].timestamp, 1_704_153_600_000);
+        assert_eq!(six.rows[0].open, 10.0);
+        assert_eq!(six.rows[0].volume, 100.0);
+    }
+
+    #[test]
+    fn reports_invalid_rows_with_their_line() {
+        let input =
+            "timestamp,open,high,low,close,volume\n1,10,11,9,10,5\n2,10,x,9,10,5\n3,10,11\n";
+        let import = read_candles(input, &CsvOptions::default());
+        assert_eq!(import.rows.len(), 1);
+        let errors: Vec<(u64, &str)> = import
+            .errors
+            .iter()
+            .map(|e| (e.line, e.message.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![(3, "invalid 'high' value 'x'"), (4, "missing 'low' column")]
+        );
+
+        let strict = CsvOptions {
+            skip_invalid_rows: false,
+            ..Default::default()
+        };
+        match read_candles_csv(input.as_bytes(), &strict) {
+            Err(CsvError::RowError(error)) => assert_eq!(error.line, 3),
+            other => panic!(
+                "expected a row error, got {:?}",
+                other.map(|i| i.rows.len())
+            ),
+        }
+    }
+
+    #[test]
+    fn candles_need_a_timestamp_column() {
+        let input = "open,high,low,close,volume\n10,11,9,10,5\n";
+        assert!(matches!(
+            read_candles_csv(input.as_bytes(), &CsvOptions::default()),
+            Err(CsvError::HeaderError(_))
+        ));
+    }
+
+    #[test]
+    fn written_candles_read_back_unchanged() {
+        let candles = vec![
+            Candle::from_ohlcv(
+                1_704_153_600_000,
+                &OHLCV {
+                    open: 10.0,
+                    high: 11.25,
+                    low: 9.5,
+                    close: 10.75,
+                    volume: 1234.5,
+                },
+            ),
+            Candle::from_ohlcv(
+                1_704_240_000_000,
+                &OHLCV {
+                    open: 10.75,
+                    high: 12.0,
+                    low: 10.5,
+                    close: 11.5,
+                    volume: 99.0,
+                },
+            ),
+        ];
+        for timestamp_format in [TimestampFormat::EpochMillis, TimestampFormat::Rfc3339] {
+            let mut buffer = Vec::new();
+            let options = CsvWriteOptions {
+                timestamp_format,
+                ..Default::default()
+            };
+            write_candles_csv(&mut buffer, &candles, &options).unwrap();
+            let import = read_candles(
+                std::str::from_utf8(&buffer).unwrap(),
+                &CsvOptions::default(),
+            );
+            assert!(import.errors.is_empty());
+            for (read, written) in import.rows.iter().zip(&candles) {
+                assert_eq!(read.timestamp, written.timestamp);
+                assert_eq!(fields(read), fields(written));
+            }
+        }
+    }
+}
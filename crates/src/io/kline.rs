@@ -0,0 +1,347 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::types::{Candle, Interval, OHLCV};
+
+/// Market data layouts understood by `parse_klines`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KlineFormat {
+    /// `[[open_time_ms, "o", "h", "l", "c", "v", close_time, ...], ...]`
+    Binance,
+    /// `{"data": [["ts_ms", "o", "h", "l", "c", "vol", ...], ...]}`, newest first.
+    Okx,
+    /// `{"result": {"list": [["start_ms", "o", "h", "l", "c", "volume", "turnover"], ...]}}`, newest first.
+    Bybit,
+    /// `{"chart": {"result": [{"timestamp": [s, ...], "indicators": {"quote": [{"open": [...], ...}]}}]}}`
+    Yahoo,
+    /// `{"t": [s, ...], "o": [...], "h": [...], "l": [...], "c": [...], "v": [...]}`
+    /// or an array of `{"t", "o", "h", "l", "c", "v"}` objects.
+    TradingView,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KlineError {
+    #[error("Parse error: {0}")]
+    ParseError(String),
+    #[error("Unexpected {0:?} layout: {1}")]
+    FormatError(KlineFormat, String),
+}
+
+/// Parses an exchange response into candles ordered from oldest to newest.
+pub fn parse_klines(value: &Value, format: KlineFormat) -> Result<Vec<Candle>, KlineError> {
+    let mut candles = match format {
+        KlineFormat::Binance => parse_rows(value, format)?,
+        KlineFormat::Okx => parse_rows(value.get("data").unwrap_or(value), format)?,
+        KlineFormat::Bybit => parse_rows(
+            value
+                .get("result")
+                .and_then(|result| result.get("list"))
+                .unwrap_or(value),
+            format,
+        )?,
+        KlineFormat::Yahoo => parse_yahoo(value)?,
+        KlineFormat::TradingView => parse_tradingview(value)?,
+    };
+    candles.sort_by_key(|candle| candle.timestamp);
+    if let Some(interval) = Interval::detect(&candles) {
+        for candle in &mut candles {
+            candle.interval = Some(interval);
+        }
+    }
+    Ok(candles)
+}
+
+pub fn parse_klines_str(json: &str, format: KlineFormat) -> Result<Vec<Candle>, KlineError> {
+    let value: Value = serde_json::from_str(json)
+        .map_err(|e| KlineError::ParseError(format!("Failed to parse JSON: {}", e)))?;
+    parse_klines(&value, format)
+}
+
+/// Like `parse_klines`, dropping the timestamps.
+pub fn parse_klines_ohlcv(value: &Value, format: KlineFormat) -> Result<Vec<OHLCV>, KlineError> {
+    Ok(parse_klines(value, format)?
+        .iter()
+        .map(OHLCV::from)
+        .collect())
+}
+
+/// Reads a number that exchanges may encode either as JSON number or as string.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn parse_rows(value: &Value, format: KlineFormat) -> Result<Vec<Candle>, KlineError> {
+    let rows = value.as_array().ok_or_else(|| {
+        KlineError::FormatError(format, "expected an array of klines".to_string())
+    })?;
+    rows.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let fields = row
+                .as_array()
+                .filter(|fields| fields.len() >= 6)
+                .ok_or_else(|| {
+                    KlineError::ParseError(format!(
+                        "Element {} is not a kline array of 6+ fields",
+                        i
+                    ))
+                })?;
+            let field = |index: usize, name: &str| {
+                number(&fields[index]).ok_or_else(|| {
+                    KlineError::ParseError(format!(
+                        "Element {} missing or invalid '{}' field",
+                        i, name
+                    ))
+                })
+            };
+            Ok(Candle::from_ohlcv(
+                field(0, "timestamp")? as i64,
+                &OHLCV {
+                    open: field(1, "open")?,
+                    high: field(2, "high")?,
+                    low: field(3, "low")?,
+                    close: field(4, "close")?,
+                    volume: field(5, "volume")?,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Builds candles from parallel columns; rows with a null in any column are skipped,
+/// as Yahoo reports untraded periods that way.
+fn from_columns(timestamps: &[Value], columns: [&[Value]; 5], timestamp_scale: i64) -> Vec<Candle> {
+    timestamps
+        .iter()
+        .enumerate()
+        .filter_map(|(i, timestamp)| {
+            let value = |column: &[Value]| column.get(i).and_then(number);
+            Some(Candle::from_ohlcv(
+                number(timestamp)? as i64 * timestamp_scale,
+                &OHLCV {
+                    open: value(columns[0])?,
+                    high: value(columns[1])?,
+                    low: value(columns[2])?,
+                    close: value(columns[3])?,
+                    volume: value(columns[4])?,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn array<'a>(value: &'a Value, key: &str, format: KlineFormat) -> Result<&'a [Value], KlineError> {
+    value
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|v| v.as_slice())
+        .ok_or_else(|| KlineError::FormatError(format, format!("missing '{}' array", key)))
+}
+
+fn parse_yahoo(value: &Value) -> Result<Vec<Candle>, KlineError> {
+    let format = KlineFormat::Yahoo;
+    let result = value.pointer("/chart/result/0").unwrap_or(value);
+    let quote = result
+        .pointer("/indicators/quote/0")
+        .ok_or_else(|| KlineError::FormatError(format, "missing 'indicators.quote'".to_string()))?;
+    Ok(from_columns(
+        array(result, "timestamp", format)?,
+        [
+            array(quote, "open", format)?,
+            array(quote, "high", format)?,
+            array(quote, "low", format)?,
+            array(quote, "close", format)?,
+            array(quote, "volume", format)?,
+        ],
+        1000,
+    ))
+}
+
+fn parse_tradingview(value: &Value) -> Result<Vec<Candle>, KlineError> {
+    let format = KlineFormat::TradingView;
+    if let Some(rows) = value.as_array() {
+        return rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let field = |key: &str| {
+                    row.get(key).and_then(number).ok_or_else(|| {
+                        KlineError::ParseError(format!(
+                            "Element {} missing or invalid '{}' field",
+                            i, key
+                        ))
+                    })
+                };
+                Ok(Candle::from_ohlcv(
+                    field("t")? as i64 * 1000,
+                    &OHLCV {
+                        open: field("o")?,
+                        high: field("h")?,
+                        low: field("l")?,
+                        close: field("c")?,
+                        volume: field("v")?,
+                    },
+                ))
+            })
+            .collect();
+    }
+    if value.get("s").and_then(|s| s.as_str()) == Some("no_data") {
+        return Ok(Vec::new());
+    }
+    Ok(from_columns(
+        array(value, "t", format)?,
+        [
+            array(value, "o", format)?,
+            array(value, "h", format)?,
+            array(value, "l", format)?,
+            array(value, "c", format)?,
+            array(value, "v", format)?,
+        ],
+        1000,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const HOUR: i64 = 3_600_000;
+    /// 2024-01-02T00:00:00Z.
+    const START: i64 = 1_704_153_600_000;
+
+    fn fields(candle: &Candle) -> (i64, f64, f64, f64, f64, f64) {
+        (
+            candle.timestamp,
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            candle.volume,
+        )
+    }
+
+    fn expected() -> Vec<(i64, f64, f64, f64, f64, f64)> {
+        vec![
+            (START, 10.0, 11.0, 9.0, 10.5, 100.0),
+            (START + HOUR, 10.5, 12.0, 10.0, 11.5, 200.0),
+        ]
+    }
+
+    fn assert_parsed(value: Value, format: KlineFormat) {
+        let candles = parse_klines(&value, format).unwrap();
+        assert_eq!(candles.iter().map(fields).collect::<Vec<_>>(), expected());
+        assert!(candles.iter().all(|c| c.interval == Some(Interval::H1)));
+    }
+
+    #[test]
+    fn parses_binance_rows() {
+        assert_parsed(
+            json!([
+                [
+                    START,
+                    "10",
+                    "11",
+                    "9",
+                    "10.5",
+                    "100",
+                    START + HOUR - 1,
+                    "0",
+                    10
+                ],
+                [
+                    START + HOUR,
+                    "10.5",
+                    "12",
+                    "10",
+                    "11.5",
+                    "200",
+                    START + 2 * HOUR - 1,
+                    "0",
+                    12
+                ]
+            ]),
+            KlineFormat::Binance,
+        );
+    }
+
+    #[test]
+    fn parses_okx_rows_newest_first() {
+        assert_parsed(
+            json!({"code": "0", "data": [
+                [(START + HOUR).to_string(), "10.5", "12", "10", "11.5", "200", "0", "0", "1"],
+                [START.to_string(), "10", "11", "9", "10.5", "100", "0", "0", "1"]
+            ]}),
+            KlineFormat::Okx,
+        );
+    }
+
+    #[test]
+    fn parses_bybit_rows_newest_first() {
+        assert_parsed(
+            json!({"retCode": 0, "result": {"category": "spot", "list": [
+                [(START + HOUR).to_string(), "10.5", "12", "10", "11.5", "200", "2300"],
+                [START.to_string(), "10", "11", "9", "10.5", "100", "1050"]
+            ]}}),
+            KlineFormat::Bybit,
+        );
+    }
+
+    #[test]
+    fn parses_yahoo_columns_skipping_untraded_periods() {
+        let seconds = START / 1000;
+        assert_parsed(
+            json!({"chart": {"result": [{
+                "timestamp": [seconds, seconds + 1800, seconds + 3600],
+                "indicators": {"quote": [{
+                    "open": [10, null, 10.5],
+                    "high": [11, null, 12],
+                    "low": [9, null, 10],
+                    "close": [10.5, null, 11.5],
+                    "volume": [100, null, 200]
+                }]}
+            }]}}),
+            KlineFormat::Yahoo,
+        );
+    }
+
+    #[test]
+    fn parses_tradingview_columns_and_rows() {
+        let seconds = START / 1000;
+        assert_parsed(
+            json!({"s": "ok", "t": [seconds, seconds + 3600], "o": [10, 10.5], "h": [11, 12],
+                "l": [9, 10], "c": [10.5, 11.5], "v": [100, 200]}),
+            KlineFormat::TradingView,
+        );
+        assert_parsed(
+            json!([
+                {"t": seconds, "o": 10, "h": 11, "l": 9, "c": 10.5, "v": 100},
+                {"t": seconds + 3600, "o": 10.5, "h": 12, "l": 10, "c": 11.5, "v": 200}
+            ]),
+            KlineFormat::TradingView,
+        );
+        let empty = parse_klines(&json!({"s": "no_data"}), KlineFormat::TradingView).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn reports_the_offending_element() {
+        let error = parse_klines_str(
+            r#"[[1, "10", "11", "9", "10", "5"], [2, "10", "bad", "9", "10", "5"]]"#,
+            KlineFormat::Binance,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Parse error: Element 1 missing or invalid 'high' field"
+        );
+        assert!(matches!(
+            parse_klines(&json!({"chart": {}}), KlineFormat::Yahoo),
+            Err(KlineError::FormatError(KlineFormat::Yahoo, _))
+        ));
+    }
+}
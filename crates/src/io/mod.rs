@@ -0,0 +1,73 @@
+pub mod csv;
+pub mod kline;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Timestamps below this many milliseconds (~1973) are taken as epoch seconds by `Auto`.
+const EPOCH_MILLIS_THRESHOLD: f64 = 1e11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimestampFormat {
+    EpochSeconds,
+    EpochMillis,
+    /// e.g. `2024-01-02T15:04:05Z`; times without an offset such as `2024-01-02 15:04:05`
+    /// are read as UTC, and a bare `2024-01-02` date as UTC midnight.
+    Rfc3339,
+    /// Numbers are read as seconds or milliseconds by magnitude, anything else as RFC3339.
+    Auto,
+}
+
+/// Parses a timestamp into epoch milliseconds.
+pub fn parse_timestamp(value: &str, format: TimestampFormat) -> Result<i64, String> {
+    let value = value.trim();
+    let numeric = || {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .ok_or_else(|| format!("invalid epoch timestamp '{}'", value))
+    };
+    match format {
+        TimestampFormat::EpochSeconds => Ok((numeric()? * 1000.0).round() as i64),
+        TimestampFormat::EpochMillis => Ok(numeric()?.round() as i64),
+        TimestampFormat::Rfc3339 => parse_rfc3339(value),
+        TimestampFormat::Auto => match numeric() {
+            Ok(n) if n.abs() < EPOCH_MILLIS_THRESHOLD => Ok((n * 1000.0).round() as i64),
+            Ok(n) => Ok(n.round() as i64),
+            Err(_) => parse_rfc3339(value),
+        },
+    }
+}
+
+/// Formats epoch milliseconds; `Auto` writes milliseconds.
+pub fn format_timestamp(millis: i64, format: TimestampFormat) -> String {
+    match format {
+        TimestampFormat::EpochSeconds => (millis / 1000).to_string(),
+        TimestampFormat::EpochMillis | TimestampFormat::Auto => millis.to_string(),
+        TimestampFormat::Rfc3339 => DateTime::<Utc>::from_timestamp_millis(millis)
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_else(|| millis.to_string()),
+    }
+}
+
+fn parse_rfc3339(value: &str) -> Result<i64, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.timestamp_millis());
+    }
+    if let Some(dt) = [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    {
+        return Ok(dt.and_utc().timestamp_millis());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc().timestamp_millis())
+        .ok_or_else(|| format!("invalid RFC3339 timestamp '{}'", value))
+}
@@ -0,0 +1,333 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, OnceLock, RwLock},
+};
+
+use crate::{
+    analysis::AnalysisType,
+    serialization::OhlcvFormat,
+    types::{ChatMessage, ChatRole},
+};
+
+/// Response language requested from the model.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    SimplifiedChinese,
+    /// A language registered with `register_language`, by code.
+    Other(String),
+}
+
+impl Language {
+    pub fn code(&self) -> &str {
+        match self {
+            Self::English => "en",
+            Self::SimplifiedChinese => "zh-CN",
+            Self::Other(code) => code,
+        }
+    }
+
+    /// Accepts the TS `i18n` values (`en`, `cn`) as well as language codes.
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "en" | "en-US" | "en-GB" => Self::English,
+            "cn" | "zh" | "zh-CN" | "zh-Hans" => Self::SimplifiedChinese,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// Localized prompt texts. Placeholders in braces are filled in by the clients:
+/// `{instruction}`, `{data_info}`, `{count}`, `{data}`, `{message}` and `{shape}` in the
+/// analysis prompts, and the ones listed on the remaining fields.
+#[derive(Debug, Clone)]
+pub struct LanguagePack {
+    /// Appended to system prompts, e.g. "Please respond in English only."
+    pub response_instruction: String,
+    pub trend_instruction: String,
+    pub volume_instruction: String,
+    pub technical_instruction: String,
+    pub comprehensive_instruction: String,
+    /// Heading of the locally computed data characteristics.
+    pub data_characteristics: String,
+    pub analysis_system_prompt: String,
+    pub analysis_user_prompt: String,
+    pub analysis_question_prompt: String,
+    pub structured_system_prompt: String,
+    pub structured_user_prompt: String,
+    pub additional_request: String,
+    /// Layout of `OhlcvFormat::CompactJson` data, added after the data in analysis prompts.
+    pub compact_format_note: String,
+    /// Layout of `OhlcvFormat::Delta` data, added after the data in analysis prompts.
+    pub delta_format_note: String,
+    /// `OhlcvSummary` statistics: `{periods}`, `{low}`, `{high}`, `{change}`,
+    /// `{change_percent}`, `{average_volume}`, `{median_volume}`, `{up_bars}`,
+    /// `{down_bars}`, `{volatility}`, `{drawdown}`, `{slope}` and `{slope_percent}`.
+    pub summary: String,
+    /// Heading of the detected candlestick patterns, `{recent}` being the window in bars.
+    pub patterns_heading: String,
+    /// One detected pattern: `{name}`, `{direction}`, `{bars}` and `{strength}`.
+    pub pattern: String,
+    /// `{bars}` of a single-bar pattern: `{index}`.
+    pub pattern_bar: String,
+    /// `{bars}` of a multi-bar pattern: `{start}` and `{end}`.
+    pub pattern_bars: String,
+    /// Heading of the detected support and resistance levels.
+    pub levels_heading: String,
+    /// One level: `{kind}`, `{price}`, `{touches}` and `{sources}`.
+    pub level: String,
+    /// Heading of the indicator context in `predict_ohlcv` prompts.
+    pub indicators_heading: String,
+    /// One indicator: `{label}`, `{latest}`, `{count}` and `{tail}`.
+    pub indicator: String,
+    /// How to read `PriceEncoding::PercentChange` data in `predict_ohlcv` prompts.
+    pub percent_change_note: String,
+    /// How to read `PriceEncoding::ZScore` data in `predict_ohlcv` prompts.
+    pub zscore_note: String,
+    /// Heading of a one-row example reply.
+    pub example_single: String,
+    /// Heading of an example reply showing all `{count}` rows.
+    pub example_multiple: String,
+    /// Heading of an example reply showing 2 of `{count}` rows, `{more}` being left out.
+    pub example_partial: String,
+    /// Row of the JSON example reply standing for the `{more}` objects left out.
+    pub example_more: String,
+    /// `predict_ohlcv` rule asking for the rows inside a JSON object keyed by `{key}`.
+    pub response_wrapper: String,
+    /// Heading of the gaps left in the series: `{missing}` bars in `{gaps}` gaps.
+    pub gaps_heading: String,
+    /// Heading of the gaps filled with synthetic bars: `{missing}` bars in `{gaps}` gaps.
+    pub filled_gaps_heading: String,
+    /// One gap: `{missing}` bars from `{start}` to `{end}`.
+    pub gap: String,
+}
+
+impl LanguagePack {
+    pub fn english() -> Self {
+        Self {
+            response_instruction: "Please respond in English only.".to_string(),
+            trend_instruction: AnalysisType::Trend.instruction().to_string(),
+            volume_instruction: AnalysisType::Volume.instruction().to_string(),
+            technical_instruction: AnalysisType::Technical.instruction().to_string(),
+            comprehensive_instruction: AnalysisType::Comprehensive.instruction().to_string(),
+            data_characteristics: "Data characteristics:".to_string(),
+            analysis_system_prompt: r#"You are a professional financial data analyst. Your task is to analyze OHLCV (Open, High, Low, Close, Volume) data and provide insights.
+Analysis focus: {instruction}
+{data_info}
+Please provide:
+1. Clear and structured analysis
+2. Key observations from the data
+3. Potential implications or insights
+4. Recommendations or considerations (if applicable)
+Only refer to patterns and levels listed above as detected; do not invent others.
+Format your response as a well-organized text analysis."#
+                .to_string(),
+            analysis_user_prompt: "Here is the OHLCV data ({count} periods):\n{data}\nPlease analyze this data as requested.".to_string(),
+            analysis_question_prompt: "Here is the OHLCV data ({count} periods):\n{data}\nMy specific question or request: {message}\nPlease analyze this data considering my request above.".to_string(),
+            structured_system_prompt: r#"You are a professional financial data analyst. Analyze the OHLCV data and provide a structured response with:
+1. Summary (brief overview)
+2. Details (key observations, 3-5 points)
+3. Recommendations (actionable insights, 2-3 points)
+4. Trend (overall direction: bullish, bearish or sideways)
+5. Confidence (a number between 0 and 1)
+6. Key levels (important support and resistance prices)
+Analysis focus: {instruction}
+{data_info}Format as JSON only, without markdown: {shape}"#
+                .to_string(),
+            structured_user_prompt: "Analyze this OHLCV data ({count} periods):\n{data}".to_string(),
+            additional_request: "\n\nAdditional request: {message}".to_string(),
+            compact_format_note: "Each row is [open, high, low, close, volume].".to_string(),
+            delta_format_note: "The first row is [open, high, low, close, volume]; every later row is the change from the previous row.".to_string(),
+            summary: "This dataset contains {periods} periods of OHLCV data.
+Price range: {low} - {high}
+Overall price change: {change} ({change_percent}%)
+Average volume: {average_volume} (median {median_volume})
+Up/down bars: {up_bars}/{down_bars}
+Realized volatility per period: {volatility}%
+Max drawdown: {drawdown}%
+Trend slope: {slope} per period ({slope_percent}%)"
+                .to_string(),
+            patterns_heading: "Candlestick patterns detected locally in the last {recent} bars (bar 0 is the oldest):".to_string(),
+            pattern: "{name} ({direction}) at {bars}, strength {strength}".to_string(),
+            pattern_bar: "bar {index}".to_string(),
+            pattern_bars: "bars {start}-{end}".to_string(),
+            levels_heading: "Support and resistance levels detected locally (strongest first):".to_string(),
+            level: "{kind} at {price} ({touches} touches, from {sources})".to_string(),
+            indicators_heading: "Computed context for the historical data, in absolute prices and volumes (indicator tails oldest to newest):".to_string(),
+            indicator: "{label}: {latest} (last {count}: {tail})".to_string(),
+            percent_change_note: "All prices are encoded as percent change relative to the last close (0 = last close, 1.5 = 1.5% above it). Volume is encoded as a multiple of the average volume. Return predictions in the same encoding.".to_string(),
+            zscore_note: "All prices are encoded as the number of standard deviations of close from the last close (0 = last close). Volume is encoded as a multiple of the average volume. Return predictions in the same encoding.".to_string(),
+            example_single: "Example of valid response for 1 period:".to_string(),
+            example_multiple: "Example of valid response for {count} periods:".to_string(),
+            example_partial: "Example of valid response for {count} periods (first 2 shown, {more} more rows following the same pattern):".to_string(),
+            example_more: "{more} more OHLCV objects following the same pattern".to_string(),
+            response_wrapper: "9. Wrap the array in a JSON object: {\"{key}\": <array>}".to_string(),
+            gaps_heading: "Note: the series has {missing} missing period(s) in {gaps} gap(s):".to_string(),
+            filled_gaps_heading: "Note: {missing} missing period(s) in {gaps} gap(s) were filled with synthetic bars:".to_string(),
+            gap: "{missing} missing bar(s) from {start} to {end}".to_string(),
+        }
+    }
+
+    pub fn simplified_chinese() -> Self {
+        Self {
+            response_instruction: "请使用中文回答。".to_string(),
+            trend_instruction: "提供详细的OHLCV数据趋势分析，包括价格方向、支撑/阻力位和趋势强度。".to_string(),
+            volume_instruction: "分析OHLCV数据中的成交量模式，包括成交量趋势、异常成交量波动和量价关系。".to_string(),
+            technical_instruction: "对OHLCV数据进行技术分析，识别潜在的技术指标、图表形态和交易信号。".to_string(),
+            comprehensive_instruction: "提供全面的OHLCV数据分析，涵盖趋势、成交量、技术面和潜在市场影响。".to_string(),
+            data_characteristics: "数据特征：".to_string(),
+            analysis_system_prompt: r#"您是一位专业的金融数据分析师。您的任务是分析OHLCV（开盘价、最高价、最低价、收盘价、成交量）数据并提供见解。
+分析重点：{instruction}
+{data_info}
+请提供：
+1. 清晰且有结构的分析
+2. 数据的关键观察结果
+3. 潜在的启示或见解
+4. 建议或注意事项（如适用）
+请以组织良好的文本分析形式回复。"#
+                .to_string(),
+            analysis_user_prompt: "这是OHLCV数据（{count}个周期）：\n{data}\n请按要求分析这些数据。".to_string(),
+            analysis_question_prompt: "这是OHLCV数据（{count}个周期）：\n{data}\n我的具体问题或需求：{message}\n请根据我的上述需求分析这些数据。".to_string(),
+            structured_system_prompt: r#"您是一位专业的金融数据分析师。分析OHLCV数据并提供结构化响应：
+1. 总结（简要概述）
+2. 详情（关键观察结果，3-5点）
+3. 建议（可操作的见解，2-3点）
+4. 趋势（整体方向：bullish、bearish 或 sideways）
+5. 置信度（0到1之间的数字）
+6. 关键价位（重要的支撑位和阻力位）
+分析重点：{instruction}
+{data_info}仅以JSON格式回复，不要使用markdown，键名和枚举值保持英文：{shape}"#
+                .to_string(),
+            structured_user_prompt: "分析此OHLCV数据（{count}个周期）：\n{data}".to_string(),
+            additional_request: "\n\n附加要求：{message}".to_string(),
+            compact_format_note: "每行为 [open, high, low, close, volume]。".to_string(),
+            delta_format_note: "第一行为 [open, high, low, close, volume]；之后每行为相对上一行的变化量。".to_string(),
+            summary: "该数据集包含{periods}个周期的OHLCV数据。
+价格区间：{low} - {high}
+整体价格变化：{change}（{change_percent}%）
+平均成交量：{average_volume}（中位数 {median_volume}）
+上涨/下跌K线：{up_bars}/{down_bars}
+每周期已实现波动率：{volatility}%
+最大回撤：{drawdown}%
+趋势斜率：每周期 {slope}（{slope_percent}%）"
+                .to_string(),
+            patterns_heading: "最近{recent}根K线中本地识别出的K线形态（第0根为最早）：".to_string(),
+            pattern: "{name}（{direction}），位于{bars}，强度 {strength}".to_string(),
+            pattern_bar: "第{index}根".to_string(),
+            pattern_bars: "第{start}-{end}根".to_string(),
+            levels_heading: "本地识别出的支撑位和阻力位（按强度从高到低）：".to_string(),
+            level: "{kind} {price}（触及{touches}次，来源：{sources}）".to_string(),
+            indicators_heading: "基于历史数据计算的参考信息，均为绝对价格和成交量（指标序列按从旧到新排列）：".to_string(),
+            indicator: "{label}：{latest}（最近{count}个：{tail}）".to_string(),
+            percent_change_note: "所有价格均编码为相对最后收盘价的百分比变化（0 = 最后收盘价，1.5 = 高于其1.5%）。成交量编码为平均成交量的倍数。请以相同编码返回预测。".to_string(),
+            zscore_note: "所有价格均编码为收盘价偏离最后收盘价的标准差倍数（0 = 最后收盘价）。成交量编码为平均成交量的倍数。请以相同编码返回预测。".to_string(),
+            example_single: "1个周期的有效响应示例：".to_string(),
+            example_multiple: "{count}个周期的有效响应示例：".to_string(),
+            example_partial: "{count}个周期的有效响应示例（仅显示前2行，其余{more}行遵循相同格式）：".to_string(),
+            example_more: "其余{more}个OHLCV对象遵循相同格式".to_string(),
+            response_wrapper: "9. 将数组包装在JSON对象中：{\"{key}\": <数组>}".to_string(),
+            gaps_heading: "注意：该序列有{gaps}处缺口，共缺失{missing}个周期：".to_string(),
+            filled_gaps_heading: "注意：{gaps}处缺口中缺失的{missing}个周期已用合成K线补齐：".to_string(),
+            gap: "{start} 至 {end} 缺失{missing}根K线".to_string(),
+        }
+    }
+
+    pub fn analysis_instruction(&self, analysis_type: AnalysisType) -> &str {
+        match analysis_type {
+            AnalysisType::Trend => &self.trend_instruction,
+            AnalysisType::Volume => &self.volume_instruction,
+            AnalysisType::Technical => &self.technical_instruction,
+            AnalysisType::Comprehensive => &self.comprehensive_instruction,
+        }
+    }
+
+    /// How to read rows of `data_format`; empty for formats that label their columns.
+    pub fn format_note(&self, data_format: OhlcvFormat) -> &str {
+        match data_format {
+            OhlcvFormat::CompactJson => &self.compact_format_note,
+            OhlcvFormat::Delta => &self.delta_format_note,
+            OhlcvFormat::Json | OhlcvFormat::Csv | OhlcvFormat::FixedPrecision { .. } => "",
+        }
+    }
+}
+
+fn registry() -> &'static RwLock<HashMap<String, LanguagePack>> {
+    static LANGUAGES: OnceLock<RwLock<HashMap<String, LanguagePack>>> = OnceLock::new();
+    LANGUAGES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Registers (or replaces) the texts used for `Language::from_code(code)`.
+pub fn register_language(code: &str, pack: LanguagePack) {
+    let code = Language::from_code(code).code().to_string();
+    registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(code, pack);
+}
+
+/// Texts for `language`. Fails for a `Language::Other` that was never registered.
+pub fn language_pack(language: &Language) -> Result<LanguagePack, String> {
+    if let Some(pack) = registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(language.code())
+    {
+        return Ok(pack.clone());
+    }
+    match language {
+        Language::English => Ok(LanguagePack::english()),
+        Language::SimplifiedChinese => Ok(LanguagePack::simplified_chinese()),
+        Language::Other(code) => Err(format!(
+            "No language pack registered for '{}'; call register_language first",
+            code
+        )),
+    }
+}
+
+/// A `{name}` placeholder in prompt texts and templates.
+pub(crate) static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{(\w+)\}").unwrap());
+
+/// Replaces `{name}` placeholders with their values in a single pass, so values
+/// containing braces are never expanded. Unknown placeholders are left as is.
+pub(crate) fn fill(template: &str, variables: &[(&str, &str)]) -> String {
+    PLACEHOLDER
+        .replace_all(template, |caps: &regex::Captures| {
+            variables
+                .iter()
+                .find(|(name, _)| *name == &caps[1])
+                .map(|(_, value)| value.to_string())
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// Appends the response instruction to every system message, or prepends one
+/// when there is none.
+pub(crate) fn apply_language(
+    messages: &[ChatMessage],
+    language: &Language,
+) -> Result<Vec<ChatMessage>, String> {
+    let instruction = language_pack(language)?.response_instruction;
+    let mut messages = messages.to_vec();
+    if messages.iter().any(|m| matches!(m.role, ChatRole::System)) {
+        for message in messages
+            .iter_mut()
+            .filter(|m| matches!(m.role, ChatRole::System))
+        {
+            message.content = format!("{}\n{}", message.content, instruction);
+        }
+    } else {
+        messages.insert(
+            0,
+            ChatMessage {
+                role: ChatRole::System,
+                content: instruction,
+            },
+        );
+    }
+    Ok(messages)
+}
@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    language::{LanguagePack, fill},
+    types::OHLCV,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelKind {
+    /// At or below the last close.
+    Support,
+    /// Above the last close.
+    Resistance,
+}
+
+impl LevelKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Support => "support",
+            Self::Resistance => "resistance",
+        }
+    }
+}
+
+impl std::str::FromStr for LevelKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "support" => Ok(Self::Support),
+            "resistance" => Ok(Self::Resistance),
+            _ => Err(format!("unknown level kind '{}'", s)),
+        }
+    }
+}
+
+/// Method that proposed a level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LevelSource {
+    /// Local high or low of the series.
+    Swing,
+    /// Price bucket with heavy traded volume.
+    Volume,
+    /// Classic floor pivot point of the last bar.
+    Pivot,
+}
+
+impl LevelSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Swing => "swing",
+            Self::Volume => "volume",
+            Self::Pivot => "pivot",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Level {
+    pub price: f64,
+    pub kind: LevelKind,
+    pub sources: Vec<LevelSource>,
+    /// Bars whose range reaches the level within the tolerance.
+    pub touches: usize,
+    /// Total volume of the touching bars.
+    pub volume: f64,
+    /// Ranking score; higher is more significant.
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelOptions {
+    /// Bars on each side a swing high or low must dominate.
+    pub swing_window: usize,
+    /// Relative distance within which candidates merge and bars count as touches.
+    pub tolerance: f64,
+    /// Number of buckets of the volume profile.
+    pub volume_bins: usize,
+    /// Number of volume buckets proposed as levels.
+    pub volume_levels: usize,
+    pub include_pivots: bool,
+    pub max_levels: usize,
+}
+
+impl Default for LevelOptions {
+    fn default() -> Self {
+        Self {
+            swing_window: 2,
+            tolerance: 0.005,
+            volume_bins: 24,
+            volume_levels: 3,
+            include_pivots: true,
+            max_levels: 6,
+        }
+    }
+}
+
+/// Classic floor pivots computed from one bar.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PivotPoints {
+    pub pivot: f64,
+    pub r1: f64,
+    pub r2: f64,
+    pub r3: f64,
+    pub s1: f64,
+    pub s2: f64,
+    pub s3: f64,
+}
+
+impl PivotPoints {
+    pub fn from_bar(bar: &OHLCV) -> Self {
+        let pivot = (bar.high + bar.low + bar.close) / 3.0;
+        let range = bar.high - bar.low;
+        Self {
+            pivot,
+            r1: 2.0 * pivot - bar.low,
+            r2: pivot + range,
+            r3: bar.high + 2.0 * (pivot - bar.low),
+            s1: 2.0 * pivot - bar.high,
+            s2: pivot - range,
+            s3: bar.low - 2.0 * (bar.high - pivot),
+        }
+    }
+
+    pub fn levels(&self) -> [f64; 7] {
+        [
+            self.s3, self.s2, self.s1, self.pivot, self.r1, self.r2, self.r3,
+        ]
+    }
+}
+
+/// Swing highs and lows: bars whose high (low) is the extreme of the `window` bars on each side.
+pub fn swing_points(ohlcv_array: &[OHLCV], window: usize) -> Vec<f64> {
+    let window = window.max(1);
+    let mut points = Vec::new();
+    for index in window..ohlcv_array.len().saturating_sub(window) {
+        let around = &ohlcv_array[index - window..=index + window];
+        let bar = &ohlcv_array[index];
+        if around.iter().all(|other| other.high <= bar.high) {
+            points.push(bar.high);
+        }
+        if around.iter().all(|other| other.low >= bar.low) {
+            points.push(bar.low);
+        }
+    }
+    points
+}
+
+/// Centers of the `count` heaviest buckets of a volume-by-price profile built from typical prices.
+pub fn volume_levels(ohlcv_array: &[OHLCV], bins: usize, count: usize) -> Vec<f64> {
+    let low = ohlcv_array
+        .iter()
+        .map(|bar| bar.low)
+        .fold(f64::MAX, f64::min);
+    let high = ohlcv_array
+        .iter()
+        .map(|bar| bar.high)
+        .fold(f64::MIN, f64::max);
+    let bins = bins.max(1);
+    if !high.is_finite() || !low.is_finite() || high <= low {
+        return Vec::new();
+    }
+    let width = (high - low) / bins as f64;
+    let mut profile = vec![0.0; bins];
+    for bar in ohlcv_array {
+        let typical = (bar.high + bar.low + bar.close) / 3.0;
+        let bucket = (((typical - low) / width) as usize).min(bins - 1);
+        profile[bucket] += bar.volume.max(0.0);
+    }
+    let mut buckets: Vec<usize> = (0..bins).filter(|b| profile[*b] > 0.0).collect();
+    buckets.sort_by(|a, b| profile[*b].total_cmp(&profile[*a]));
+    buckets
+        .into_iter()
+        .take(count)
+        .map(|bucket| low + width * (bucket as f64 + 0.5))
+        .collect()
+}
+
+/// Finds candidate levels from swings, the volume profile and pivots, merges candidates
+/// closer than `tolerance`, and returns them ranked by score.
+pub fn detect_levels(ohlcv_array: &[OHLCV], options: &LevelOptions) -> Vec<Level> {
+    let Some(last) = ohlcv_array.last() else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<(f64, LevelSource)> = swing_points(ohlcv_array, options.swing_window)
+        .into_iter()
+        .map(|price| (price, LevelSource::Swing))
+        .collect();
+    candidates.extend(
+        volume_levels(ohlcv_array, options.volume_bins, options.volume_levels)
+            .into_iter()
+            .map(|price| (price, LevelSource::Volume)),
+    );
+    if options.include_pivots {
+        candidates.extend(
+            PivotPoints::from_bar(last)
+                .levels()
+                .into_iter()
+                .map(|price| (price, LevelSource::Pivot)),
+        );
+    }
+    candidates.retain(|(price, _)| price.is_finite() && *price > 0.0);
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Greedy clustering: a candidate joins the current cluster while it stays within
+    // tolerance of the cluster's mean price.
+    let mut clusters: Vec<Vec<(f64, LevelSource)>> = Vec::new();
+    for candidate in candidates {
+        match clusters.last_mut() {
+            Some(cluster)
+                if (candidate.0 - mean_price(cluster)).abs() <= options.tolerance * candidate.0 =>
+            {
+                cluster.push(candidate)
+            }
+            _ => clusters.push(vec![candidate]),
+        }
+    }
+
+    let total_volume: f64 = ohlcv_array.iter().map(|bar| bar.volume.max(0.0)).sum();
+    let mut levels: Vec<Level> = clusters
+        .iter()
+        .map(|cluster| {
+            let price = mean_price(cluster);
+            let mut sources: Vec<LevelSource> = cluster.iter().map(|(_, s)| *s).collect();
+            sources.sort();
+            sources.dedup();
+            let band = options.tolerance * price;
+            let touching = ohlcv_array
+                .iter()
+                .filter(|bar| bar.low <= price + band && bar.high >= price - band);
+            let (touches, volume) =
+                touching.fold((0, 0.0), |(n, v), bar| (n + 1, v + bar.volume.max(0.0)));
+            let volume_share = if total_volume > 0.0 {
+                volume / total_volume
+            } else {
+                0.0
+            };
+            Level {
+                price,
+                kind: if price <= last.close {
+                    LevelKind::Support
+                } else {
+                    LevelKind::Resistance
+                },
+                score: touches as f64 + 2.0 * (sources.len() - 1) as f64 + 10.0 * volume_share,
+                sources,
+                touches,
+                volume,
+            }
+        })
+        .collect();
+    levels.sort_by(|a, b| b.score.total_cmp(&a.score));
+    levels.truncate(options.max_levels);
+    levels
+}
+
+fn mean_price(cluster: &[(f64, LevelSource)]) -> f64 {
+    cluster.iter().map(|(price, _)| price).sum::<f64>() / cluster.len() as f64
+}
+
+/// Lists levels as a prompt note, strongest first; empty when there are none.
+pub fn describe_levels(levels: &[Level], pack: &LanguagePack) -> String {
+    if levels.is_empty() {
+        return String::new();
+    }
+    let details: Vec<String> = levels
+        .iter()
+        .map(|level| {
+            let sources: Vec<&str> = level.sources.iter().map(|source| source.as_str()).collect();
+            let line = fill(
+                &pack.level,
+                &[
+                    ("kind", level.kind.as_str()),
+                    ("price", &format!("{:.4}", level.price)),
+                    ("touches", &level.touches.to_string()),
+                    ("sources", &sources.join("+")),
+                ],
+            );
+            format!("- {}", line)
+        })
+        .collect();
+    format!("{}\n{}", pack.levels_heading, details.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Swings between 100 and 110, with highs and lows one point beyond the close.
+    fn bars() -> Vec<OHLCV> {
+        [
+            100.0, 105.0, 110.0, 105.0, 100.0, 105.0, 110.0, 105.0, 100.0, 105.0,
+        ]
+        .iter()
+        .map(|&close| OHLCV {
+            open: close,
+            high: close + 1.0,
+            low: close - 1.0,
+            close,
+            volume: 100.0,
+        })
+        .collect()
+    }
+
+    fn swings_only() -> LevelOptions {
+        LevelOptions {
+            volume_levels: 0,
+            include_pivots: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pivot_points_follow_the_floor_formulas() {
+        let pivots = PivotPoints::from_bar(&OHLCV {
+            open: 10.0,
+            high: 12.0,
+            low: 9.0,
+            close: 10.5,
+            volume: 0.0,
+        });
+        assert_eq!(pivots.levels(), [6.0, 7.5, 9.0, 10.5, 12.0, 13.5, 15.0]);
+    }
+
+    #[test]
+    fn swing_points_need_the_full_window_on_both_sides() {
+        assert_eq!(swing_points(&bars(), 2), vec![111.0, 99.0, 111.0]);
+        assert!(swing_points(&bars()[..4], 2).is_empty());
+    }
+
+    #[test]
+    fn volume_levels_pick_the_heaviest_buckets() {
+        let mut bars = bars();
+        bars[3].volume = 5_000.0;
+        let levels = volume_levels(&bars, 10, 1);
+        assert_eq!(levels.len(), 1);
+        assert!((levels[0] - 105.0).abs() <= 1.2, "{}", levels[0]);
+    }
+
+    #[test]
+    fn merges_swings_into_ranked_support_and_resistance() {
+        let levels = detect_levels(&bars(), &swings_only());
+        let found: Vec<(f64, LevelKind, usize)> = levels
+            .iter()
+            .map(|level| (level.price, level.kind, level.touches))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (99.0, LevelKind::Support, 3),
+                (111.0, LevelKind::Resistance, 2)
+            ]
+        );
+        assert!(
+            levels
+                .iter()
+                .all(|level| level.sources == [LevelSource::Swing])
+        );
+    }
+
+    #[test]
+    fn agreeing_methods_merge_into_one_level() {
+        let mut bars = bars();
+        bars[8].volume = 5_000.0;
+        let options = LevelOptions {
+            volume_bins: 12,
+            volume_levels: 1,
+            include_pivots: false,
+            tolerance: 0.02,
+            ..Default::default()
+        };
+        let levels = detect_levels(&bars, &options);
+        assert_eq!(levels[0].kind, LevelKind::Support);
+        assert_eq!(levels[0].sources, [LevelSource::Swing, LevelSource::Volume]);
+    }
+
+    #[test]
+    fn pivots_are_included_and_the_result_is_capped() {
+        let options = LevelOptions {
+            max_levels: 3,
+            ..Default::default()
+        };
+        let levels = detect_levels(&bars(), &options);
+        assert_eq!(levels.len(), 3);
+        assert!(levels.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        let all = detect_levels(
+            &bars(),
+            &LevelOptions {
+                max_levels: usize::MAX,
+                ..Default::default()
+            },
+        );
+        assert!(
+            all.iter()
+                .any(|level| level.sources.contains(&LevelSource::Pivot))
+        );
+        assert!(detect_levels(&[], &options).is_empty());
+    }
+
+    #[test]
+    fn describes_levels_strongest_first() {
+        let levels = detect_levels(&bars(), &swings_only());
+        let note = describe_levels(&levels, &LanguagePack::english());
+        assert_eq!(
+            note,
+            "Support and resistance levels detected locally (strongest first):\n\
+             - support at 99.0000 (3 touches, from swing)\n\
+             - resistance at 111.0000 (2 touches, from swing)"
+        );
+        assert!(describe_levels(&[], &LanguagePack::english()).is_empty());
+    }
+}
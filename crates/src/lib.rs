@@ -1,3 +1,23 @@
-mod aliyun;
-mod types;
-mod deepseek;
\ No newline at end of file
+pub mod aliyun;
+pub mod analysis;
+pub mod capability;
+mod chat;
+pub mod deepseek;
+pub mod encoding;
+pub mod ensemble;
+mod extract;
+pub mod gaps;
+pub mod health;
+pub mod indicators;
+pub mod io;
+pub mod language;
+pub mod levels;
+pub mod patterns;
+pub mod prompts;
+pub mod repair;
+pub mod resample;
+pub mod router;
+pub mod serialization;
+pub mod summary;
+pub mod types;
+pub mod validation;
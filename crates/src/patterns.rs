@@ -0,0 +1,516 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    language::{LanguagePack, fill},
+    types::OHLCV,
+};
+
+/// Number of bars before a pattern used to decide the prevailing trend.
+const TREND_LOOKBACK: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PatternKind {
+    Doji,
+    Hammer,
+    InvertedHammer,
+    HangingMan,
+    ShootingStar,
+    BullishEngulfing,
+    BearishEngulfing,
+    BullishHarami,
+    BearishHarami,
+    PiercingLine,
+    DarkCloudCover,
+    MorningStar,
+    EveningStar,
+    ThreeWhiteSoldiers,
+    ThreeBlackCrows,
+}
+
+impl PatternKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Doji => "doji",
+            Self::Hammer => "hammer",
+            Self::InvertedHammer => "inverted hammer",
+            Self::HangingMan => "hanging man",
+            Self::ShootingStar => "shooting star",
+            Self::BullishEngulfing => "bullish engulfing",
+            Self::BearishEngulfing => "bearish engulfing",
+            Self::BullishHarami => "bullish harami",
+            Self::BearishHarami => "bearish harami",
+            Self::PiercingLine => "piercing line",
+            Self::DarkCloudCover => "dark cloud cover",
+            Self::MorningStar => "morning star",
+            Self::EveningStar => "evening star",
+            Self::ThreeWhiteSoldiers => "three white soldiers",
+            Self::ThreeBlackCrows => "three black crows",
+        }
+    }
+
+    /// Number of bars the pattern spans.
+    pub fn bar_count(&self) -> usize {
+        match self {
+            Self::Doji
+            | Self::Hammer
+            | Self::InvertedHammer
+            | Self::HangingMan
+            | Self::ShootingStar => 1,
+            Self::BullishEngulfing
+            | Self::BearishEngulfing
+            | Self::BullishHarami
+            | Self::BearishHarami
+            | Self::PiercingLine
+            | Self::DarkCloudCover => 2,
+            Self::MorningStar
+            | Self::EveningStar
+            | Self::ThreeWhiteSoldiers
+            | Self::ThreeBlackCrows => 3,
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        match self {
+            Self::Doji => Direction::Neutral,
+            Self::Hammer
+            | Self::InvertedHammer
+            | Self::BullishEngulfing
+            | Self::BullishHarami
+            | Self::PiercingLine
+            | Self::MorningStar
+            | Self::ThreeWhiteSoldiers => Direction::Bullish,
+            Self::HangingMan
+            | Self::ShootingStar
+            | Self::BearishEngulfing
+            | Self::BearishHarami
+            | Self::DarkCloudCover
+            | Self::EveningStar
+            | Self::ThreeBlackCrows => Direction::Bearish,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Bullish,
+    Bearish,
+    Neutral,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bullish => "bullish",
+            Self::Bearish => "bearish",
+            Self::Neutral => "neutral",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    /// Index of the last bar of the pattern.
+    pub index: usize,
+    /// Index of the first bar of the pattern.
+    pub start: usize,
+    pub direction: Direction,
+    /// How clearly the bars match the textbook shape, in [0, 1].
+    pub strength: f64,
+}
+
+impl Pattern {
+    pub fn describe(&self, pack: &LanguagePack) -> String {
+        let bars = if self.start == self.index {
+            fill(&pack.pattern_bar, &[("index", &self.index.to_string())])
+        } else {
+            fill(
+                &pack.pattern_bars,
+                &[
+                    ("start", &self.start.to_string()),
+                    ("end", &self.index.to_string()),
+                ],
+            )
+        };
+        fill(
+            &pack.pattern,
+            &[
+                ("name", self.kind.as_str()),
+                ("direction", self.direction.as_str()),
+                ("bars", &bars),
+                ("strength", &format!("{:.2}", self.strength)),
+            ],
+        )
+    }
+}
+
+/// Body, shadows and range of a single bar.
+struct Shape {
+    body: f64,
+    range: f64,
+    upper: f64,
+    lower: f64,
+    bullish: bool,
+    bearish: bool,
+}
+
+impl Shape {
+    fn of(bar: &OHLCV) -> Self {
+        Self {
+            body: (bar.close - bar.open).abs(),
+            range: bar.high - bar.low,
+            upper: bar.high - bar.open.max(bar.close),
+            lower: bar.open.min(bar.close) - bar.low,
+            bullish: bar.close > bar.open,
+            bearish: bar.close < bar.open,
+        }
+    }
+
+    fn is_long(&self) -> bool {
+        self.range > 0.0 && self.body >= 0.5 * self.range
+    }
+}
+
+/// +1 when closes rose into bar `index`, -1 when they fell, 0 without enough history.
+fn trend_before(ohlcv_array: &[OHLCV], index: usize) -> i8 {
+    if index < 2 {
+        return 0;
+    }
+    let from = index.saturating_sub(TREND_LOOKBACK);
+    let change = ohlcv_array[index - 1].close - ohlcv_array[from].close;
+    if change > 0.0 {
+        1
+    } else if change < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+fn clamp01(value: f64) -> f64 {
+    if value.is_finite() {
+        value.clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Detects single-, two- and three-bar patterns, ordered by the index of their last bar.
+pub fn detect_patterns(ohlcv_array: &[OHLCV]) -> Vec<Pattern> {
+    let mut patterns = Vec::new();
+    for index in 0..ohlcv_array.len() {
+        let mut found = |kind: PatternKind, strength: f64| {
+            patterns.push(Pattern {
+                kind,
+                index,
+                start: index + 1 - kind.bar_count(),
+                direction: kind.direction(),
+                strength: clamp01(strength),
+            });
+        };
+        let bar = &ohlcv_array[index];
+        let c = Shape::of(bar);
+        // Single-bar shapes are ratios of the range; flat bars can still complete the
+        // multi-bar patterns below.
+        if c.range > 0.0 && c.range.is_finite() {
+            let trend = trend_before(ohlcv_array, index);
+            if c.body <= 0.1 * c.range {
+                found(PatternKind::Doji, 1.0 - c.body / (0.1 * c.range));
+            } else if c.body <= c.range / 3.0 {
+                let body = c.body.max(f64::EPSILON);
+                if c.lower >= 2.0 * c.body && c.upper <= c.body {
+                    let strength = (c.lower / body - 2.0) / 2.0 + 0.5;
+                    match trend {
+                        -1 => found(PatternKind::Hammer, strength),
+                        1 => found(PatternKind::HangingMan, strength),
+                        _ => {}
+                    }
+                } else if c.upper >= 2.0 * c.body && c.lower <= c.body {
+                    let strength = (c.upper / body - 2.0) / 2.0 + 0.5;
+                    match trend {
+                        -1 => found(PatternKind::InvertedHammer, strength),
+                        1 => found(PatternKind::ShootingStar, strength),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if index >= 1 {
+            let prev_bar = &ohlcv_array[index - 1];
+            let p = Shape::of(prev_bar);
+            if p.body > 0.0 {
+                if p.bearish
+                    && c.bullish
+                    && bar.open <= prev_bar.close
+                    && bar.close >= prev_bar.open
+                    && c.body > p.body
+                {
+                    found(
+                        PatternKind::BullishEngulfing,
+                        (c.body / p.body - 1.0) / 2.0 + 0.5,
+                    );
+                } else if p.bullish
+                    && c.bearish
+                    && bar.open >= prev_bar.close
+                    && bar.close <= prev_bar.open
+                    && c.body > p.body
+                {
+                    found(
+                        PatternKind::BearishEngulfing,
+                        (c.body / p.body - 1.0) / 2.0 + 0.5,
+                    );
+                }
+
+                let inside = bar.open.max(bar.close) <= prev_bar.open.max(prev_bar.close)
+                    && bar.open.min(bar.close) >= prev_bar.open.min(prev_bar.close);
+                if p.is_long() && inside && c.body < p.body {
+                    if p.bearish && c.bullish {
+                        found(PatternKind::BullishHarami, 1.0 - c.body / p.body);
+                    } else if p.bullish && c.bearish {
+                        found(PatternKind::BearishHarami, 1.0 - c.body / p.body);
+                    }
+                }
+
+                let midpoint = (prev_bar.open + prev_bar.close) / 2.0;
+                if p.is_long()
+                    && p.bearish
+                    && c.bullish
+                    && bar.open < prev_bar.close
+                    && bar.close > midpoint
+                    && bar.close < prev_bar.open
+                {
+                    found(
+                        PatternKind::PiercingLine,
+                        (bar.close - midpoint) / (p.body / 2.0),
+                    );
+                } else if p.is_long()
+                    && p.bullish
+                    && c.bearish
+                    && bar.open > prev_bar.close
+                    && bar.close < midpoint
+                    && bar.close > prev_bar.open
+                {
+                    found(
+                        PatternKind::DarkCloudCover,
+                        (midpoint - bar.close) / (p.body / 2.0),
+                    );
+                }
+            }
+        }
+
+        if index >= 2 {
+            let (first_bar, middle_bar) = (&ohlcv_array[index - 2], &ohlcv_array[index - 1]);
+            let (a, b) = (Shape::of(first_bar), Shape::of(middle_bar));
+            if a.is_long() && b.body <= 0.3 * a.body {
+                let midpoint = (first_bar.open + first_bar.close) / 2.0;
+                let star = 1.0 - b.body / (0.3 * a.body).max(f64::EPSILON);
+                if a.bearish && c.bullish && bar.close > midpoint {
+                    let recovery = (bar.close - midpoint) / (a.body / 2.0);
+                    found(PatternKind::MorningStar, (star + recovery.min(1.0)) / 2.0);
+                } else if a.bullish && c.bearish && bar.close < midpoint {
+                    let decline = (midpoint - bar.close) / (a.body / 2.0);
+                    found(PatternKind::EveningStar, (star + decline.min(1.0)) / 2.0);
+                }
+            }
+
+            let bars = [first_bar, middle_bar, bar];
+            let shapes = [a, b, c];
+            let opens_inside = |k: usize| {
+                let previous = bars[k - 1];
+                let open = bars[k].open;
+                open >= previous.open.min(previous.close)
+                    && open <= previous.open.max(previous.close)
+            };
+            let fullness = shapes
+                .iter()
+                .map(|s| s.body / s.range.max(f64::EPSILON))
+                .sum::<f64>()
+                / 3.0;
+            if shapes.iter().all(|s| s.bullish && s.is_long())
+                && bars[1].close > bars[0].close
+                && bar.close > bars[1].close
+                && opens_inside(1)
+                && opens_inside(2)
+            {
+                found(PatternKind::ThreeWhiteSoldiers, fullness);
+            } else if shapes.iter().all(|s| s.bearish && s.is_long())
+                && bars[1].close < bars[0].close
+                && bar.close < bars[1].close
+                && opens_inside(1)
+                && opens_inside(2)
+            {
+                found(PatternKind::ThreeBlackCrows, fullness);
+            }
+        }
+    }
+    patterns
+}
+
+/// Lists patterns ending within the last `recent` of `len` bars as a prompt note;
+/// empty when there are none.
+pub fn describe_patterns(
+    patterns: &[Pattern],
+    len: usize,
+    recent: usize,
+    pack: &LanguagePack,
+) -> String {
+    let from = len.saturating_sub(recent);
+    let details: Vec<String> = patterns
+        .iter()
+        .filter(|pattern| pattern.index >= from)
+        .map(|pattern| format!("- {}", pattern.describe(pack)))
+        .collect();
+    if details.is_empty() {
+        return String::new();
+    }
+    format!(
+        "{}\n{}",
+        fill(
+            &pack.patterns_heading,
+            &[("recent", &recent.min(len).to_string())]
+        ),
+        details.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Bar = (f64, f64, f64, f64);
+
+    const DOWNTREND: [Bar; 3] = [
+        (20.5, 20.6, 19.9, 20.0),
+        (19.5, 19.6, 18.9, 19.0),
+        (18.5, 18.6, 17.9, 18.0),
+    ];
+    const UPTREND: [Bar; 3] = [
+        (10.0, 10.6, 9.9, 10.5),
+        (11.0, 11.6, 10.9, 11.5),
+        (12.0, 12.6, 11.9, 12.5),
+    ];
+
+    fn series(prefix: &[Bar], bars: &[Bar]) -> Vec<OHLCV> {
+        prefix
+            .iter()
+            .chain(bars)
+            .map(|&(open, high, low, close)| OHLCV {
+                open,
+                high,
+                low,
+                close,
+                volume: 100.0,
+            })
+            .collect()
+    }
+
+    /// Asserts that `kind` is detected on the last bar, spanning its bar count.
+    fn assert_last(prefix: &[Bar], bars: &[Bar], kind: PatternKind) {
+        let ohlcv = series(prefix, bars);
+        let last = ohlcv.len() - 1;
+        let pattern = detect_patterns(&ohlcv)
+            .into_iter()
+            .find(|pattern| pattern.index == last && pattern.kind == kind)
+            .unwrap_or_else(|| panic!("{} not detected", kind.as_str()));
+        assert_eq!(pattern.start, last + 1 - kind.bar_count());
+        assert_eq!(pattern.direction, kind.direction());
+        assert!((0.0..=1.0).contains(&pattern.strength));
+    }
+
+    #[test]
+    fn detects_single_bar_patterns_by_trend() {
+        assert_last(&[], &[(10.0, 11.0, 9.0, 10.05)], PatternKind::Doji);
+        assert_last(&DOWNTREND, &[(17.0, 17.5, 16.0, 17.4)], PatternKind::Hammer);
+        assert_last(
+            &UPTREND,
+            &[(12.6, 13.1, 11.6, 13.0)],
+            PatternKind::HangingMan,
+        );
+        assert_last(
+            &DOWNTREND,
+            &[(17.0, 18.4, 16.9, 17.4)],
+            PatternKind::InvertedHammer,
+        );
+        assert_last(
+            &UPTREND,
+            &[(12.6, 14.0, 12.5, 13.0)],
+            PatternKind::ShootingStar,
+        );
+    }
+
+    #[test]
+    fn hammer_shapes_need_a_prior_trend() {
+        let ohlcv = series(&[], &[(17.0, 17.5, 16.0, 17.4)]);
+        assert!(detect_patterns(&ohlcv).is_empty());
+    }
+
+    #[test]
+    fn detects_two_bar_patterns() {
+        assert_last(
+            &DOWNTREND,
+            &[(17.9, 19.0, 17.8, 18.8)],
+            PatternKind::BullishEngulfing,
+        );
+        assert_last(
+            &UPTREND,
+            &[(12.6, 12.7, 11.7, 11.8)],
+            PatternKind::BearishEngulfing,
+        );
+        assert_last(
+            &DOWNTREND,
+            &[(18.1, 18.4, 18.0, 18.3)],
+            PatternKind::BullishHarami,
+        );
+        assert_last(
+            &UPTREND,
+            &[(12.4, 12.45, 12.1, 12.2)],
+            PatternKind::BearishHarami,
+        );
+        assert_last(
+            &[(20.0, 20.1, 18.9, 19.0)],
+            &[(18.8, 19.8, 18.7, 19.7)],
+            PatternKind::PiercingLine,
+        );
+        assert_last(
+            &[(10.0, 11.1, 9.9, 11.0)],
+            &[(11.2, 11.3, 10.2, 10.3)],
+            PatternKind::DarkCloudCover,
+        );
+    }
+
+    #[test]
+    fn detects_three_bar_patterns() {
+        assert_last(
+            &[(20.0, 20.1, 18.9, 19.0), (18.8, 18.9, 18.6, 18.7)],
+            &[(18.8, 19.9, 18.7, 19.8)],
+            PatternKind::MorningStar,
+        );
+        assert_last(
+            &[(10.0, 11.1, 9.9, 11.0), (11.2, 11.4, 11.1, 11.3)],
+            &[(11.2, 11.3, 10.1, 10.2)],
+            PatternKind::EveningStar,
+        );
+        assert_last(
+            &[(10.0, 10.6, 9.9, 10.5), (10.3, 11.1, 10.2, 11.0)],
+            &[(10.8, 11.6, 10.7, 11.5)],
+            PatternKind::ThreeWhiteSoldiers,
+        );
+        assert_last(
+            &[(12.0, 12.1, 11.4, 11.5), (11.7, 11.8, 10.9, 11.0)],
+            &[(11.2, 11.3, 10.4, 10.5)],
+            PatternKind::ThreeBlackCrows,
+        );
+    }
+
+    #[test]
+    fn describes_only_recent_patterns() {
+        let ohlcv = series(&UPTREND, &[(12.6, 14.0, 12.5, 13.0)]);
+        let patterns = detect_patterns(&ohlcv);
+        let pack = LanguagePack::english();
+        let note = describe_patterns(&patterns, ohlcv.len(), 2, &pack);
+        assert!(note.starts_with("Candlestick patterns detected locally in the last 2 bars"));
+        assert!(note.contains("- shooting star (bearish) at bar 3, strength"));
+        assert!(describe_patterns(&patterns, ohlcv.len() + 5, 2, &pack).is_empty());
+    }
+}
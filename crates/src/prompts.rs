@@ -0,0 +1,447 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{OnceLock, RwLock},
+};
+
+use crate::{
+    encoding::{EncodingContext, PriceEncoding},
+    language::{Language, LanguagePack, PLACEHOLDER, fill},
+    serialization::{OhlcvFormat, PREDICTIONS_KEY},
+    types::{ChatMessage, ChatRole},
+};
+
+/// Name of the template used by `predict_ohlcv`.
+pub const PREDICT_OHLCV: &str = "predict_ohlcv";
+
+/// Name of the `predict_ohlcv` template for responses in simplified Chinese.
+pub const PREDICT_OHLCV_ZH: &str = "predict_ohlcv.zh-CN";
+
+/// Variables available to the `predict_ohlcv` template, as filled in by `predict_ohlcv_messages`.
+pub fn predict_ohlcv_variables() -> Vec<&'static str> {
+    let encoding = EncodingContext::new(PriceEncoding::Raw, &[]);
+    PredictInputs {
+        instructions: "",
+        count: 1,
+        periods: 0,
+        data: "",
+        data_format: OhlcvFormat::default(),
+        encoding: &encoding,
+        indicators: "",
+        gaps: "",
+        wrapped: false,
+        language: &Language::English,
+        pack: &LanguagePack::english(),
+    }
+    .variables()
+    .into_iter()
+    .map(|(name, _)| name)
+    .collect()
+}
+
+const DEFAULT_PREDICT_SYSTEM: &str = r#"You are a professional financial data analysis AI. The user will give you an array of OHLCV (Open, High, Low, Close, Volume) data.
+Your task: {instructions}
+CRITICAL RULES:
+1. {count_message}
+2. Return ONLY {response_noun}, NO explanations, comments, or other text
+3. The OHLCV array format must match: {response_shape}
+4. All numbers must be valid numbers
+5. Ensure technical rationality (high >= low, high >= open >= low, high >= close >= low, volume >= 0)
+6. Maintain consistency with historical trends and patterns
+7. For technical analysis, provide reasonable values based on typical patterns
+8. Do not include markdown formatting, only {plain_name}
+{response_wrapper}
+{example}"#;
+
+const DEFAULT_PREDICT_USER: &str = r#"Here is the historical OHLCV data ({periods} periods):
+{data}
+Please process this data according to the system instructions. Remember to return EXACTLY {count} OHLCV object(s) {response_container} with no additional text.
+{encoding_note}
+{indicators}
+{gaps}"#;
+
+const DEFAULT_PREDICT_SYSTEM_ZH: &str = r#"您是一位专业的金融数据分析AI。用户会提供一组OHLCV（开盘价、最高价、最低价、收盘价、成交量）数据。
+您的任务：{instructions}
+重要规则：
+1. 恰好返回接下来 {count} 个周期的 {count} 个OHLCV对象
+2. 只返回数据，不要包含任何解释、注释或其他文字
+3. 数据格式必须为：{response_shape}
+4. 所有数值必须是有效数字
+5. 确保技术合理性（high >= low，high >= open >= low，high >= close >= low，volume >= 0）
+6. 与历史趋势和形态保持一致
+7. 技术分析时，根据常见形态给出合理的数值
+8. 不要使用markdown格式
+{response_wrapper}
+{example}"#;
+
+const DEFAULT_PREDICT_USER_ZH: &str = r#"这是历史OHLCV数据（{periods}个周期）：
+{data}
+请按照系统指令处理这些数据。请记住恰好返回 {count} 个OHLCV对象，不要包含其他文字。
+{encoding_note}
+{indicators}
+{gaps}"#;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("IO error: {0}")]
+    IoError(String),
+    #[error("Parse error: {0}")]
+    ParseError(String),
+    #[error("Unknown template variable: {{{0}}}")]
+    UnknownVariable(String),
+}
+
+/// A system and user message pair with `{variable}` placeholders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    pub system: String,
+    pub user: String,
+}
+
+impl PromptTemplate {
+    pub fn new(system: impl Into<String>, user: impl Into<String>) -> Self {
+        Self {
+            system: system.into(),
+            user: user.into(),
+        }
+    }
+
+    /// Built-in template for `predict_ohlcv`.
+    pub fn predict_ohlcv() -> Self {
+        Self::new(DEFAULT_PREDICT_SYSTEM, DEFAULT_PREDICT_USER)
+    }
+
+    /// Built-in `predict_ohlcv` template in simplified Chinese.
+    pub fn predict_ohlcv_zh() -> Self {
+        Self::new(DEFAULT_PREDICT_SYSTEM_ZH, DEFAULT_PREDICT_USER_ZH)
+    }
+
+    /// Parses a template with `[system]` and `[user]` section headers on their own lines.
+    pub fn parse(text: &str) -> Result<Self, TemplateError> {
+        let mut sections: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut current = None;
+        for line in text.lines() {
+            match line.trim() {
+                "[system]" => current = Some("system"),
+                "[user]" => current = Some("user"),
+                _ => match current {
+                    Some(section) => sections.entry(section).or_default().push(line),
+                    None if line.trim().is_empty() => {}
+                    None => {
+                        return Err(TemplateError::ParseError(
+                            "text before the first [system] or [user] section".to_string(),
+                        ));
+                    }
+                },
+            }
+        }
+        let mut section = |name: &str| {
+            sections
+                .remove(name)
+                .map(|lines| lines.join("\n").trim().to_string())
+                .ok_or_else(|| TemplateError::ParseError(format!("missing [{}] section", name)))
+        };
+        Ok(Self::new(section("system")?, section("user")?))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, TemplateError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| TemplateError::IoError(e.to_string()))?;
+        Self::parse(&text)
+    }
+
+    /// Placeholder names used by the template, in order of first appearance.
+    pub fn variables(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for caps in PLACEHOLDER.captures_iter(&format!("{}\n{}", self.system, self.user)) {
+            if !names.iter().any(|name| name == &caps[1]) {
+                names.push(caps[1].to_string());
+            }
+        }
+        names
+    }
+
+    /// Fails on the first placeholder not in `known`.
+    pub fn validate(&self, known: &[&str]) -> Result<(), TemplateError> {
+        match self
+            .variables()
+            .into_iter()
+            .find(|name| !known.contains(&name.as_str()))
+        {
+            Some(name) => Err(TemplateError::UnknownVariable(name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Fills both messages. A line holding only a placeholder that expands to an
+    /// empty value is dropped, so optional sections leave no blank lines.
+    pub fn render(&self, variables: &[(&str, &str)]) -> Vec<ChatMessage> {
+        let render = |text: &str| {
+            text.lines()
+                .filter(|line| {
+                    let line = line.trim();
+                    !variables
+                        .iter()
+                        .any(|(name, value)| value.is_empty() && line == format!("{{{}}}", name))
+                })
+                .map(|line| fill(line, variables))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        vec![
+            ChatMessage {
+                role: ChatRole::System,
+                content: render(&self.system),
+            },
+            ChatMessage {
+                role: ChatRole::User,
+                content: render(&self.user),
+            },
+        ]
+    }
+}
+
+fn built_in_templates() -> HashMap<String, PromptTemplate> {
+    HashMap::from([
+        (PREDICT_OHLCV.to_string(), PromptTemplate::predict_ohlcv()),
+        (
+            PREDICT_OHLCV_ZH.to_string(),
+            PromptTemplate::predict_ohlcv_zh(),
+        ),
+    ])
+}
+
+fn registry() -> &'static RwLock<HashMap<String, PromptTemplate>> {
+    static TEMPLATES: OnceLock<RwLock<HashMap<String, PromptTemplate>>> = OnceLock::new();
+    TEMPLATES.get_or_init(|| RwLock::new(built_in_templates()))
+}
+
+/// Name of the `predict_ohlcv` template for `language`, e.g. `predict_ohlcv.zh-CN`;
+/// English uses `PREDICT_OHLCV` itself.
+pub fn predict_template_name(language: &Language) -> String {
+    match language {
+        Language::English => PREDICT_OHLCV.to_string(),
+        _ => format!("{}.{}", PREDICT_OHLCV, language.code()),
+    }
+}
+
+/// Registers (or overrides) a named template for all providers. Templates for
+/// built-in names, including localized `predict_ohlcv.<code>` ones, are validated
+/// against the variables those methods provide.
+pub fn register_template(name: &str, template: PromptTemplate) -> Result<(), TemplateError> {
+    if name == PREDICT_OHLCV || name.starts_with(&format!("{}.", PREDICT_OHLCV)) {
+        template.validate(&predict_ohlcv_variables())?;
+    }
+    registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), template);
+    Ok(())
+}
+
+pub fn get_template(name: &str) -> Option<PromptTemplate> {
+    registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+}
+
+/// Restores the built-in `predict_ohlcv` templates.
+pub fn reset_templates() {
+    *registry().write().unwrap_or_else(|e| e.into_inner()) = built_in_templates();
+}
+
+/// Values a `predict_ohlcv` prompt is rendered from.
+pub(crate) struct PredictInputs<'a> {
+    pub instructions: &'a str,
+    pub count: usize,
+    pub periods: usize,
+    pub data: &'a str,
+    pub data_format: OhlcvFormat,
+    pub encoding: &'a EncodingContext,
+    pub indicators: &'a str,
+    /// Where the series has missing bars, from `GapFillResult::prompt_note`.
+    pub gaps: &'a str,
+    /// Asks for the rows inside a `{"predictions": [...]}` object, as required
+    /// when the request sets a JSON `response_format`.
+    pub wrapped: bool,
+    /// Selects the registered template, `predict_ohlcv.<code>` for non-English languages.
+    pub language: &'a Language,
+    /// Texts for the notes, example and rules filled into the template.
+    pub pack: &'a LanguagePack,
+}
+
+impl PredictInputs<'_> {
+    fn variables(&self) -> Vec<(&'static str, String)> {
+        let count = self.count;
+        let count_message = if count == 1 {
+            "Return EXACTLY 1 OHLCV object for the next period.".to_string()
+        } else {
+            format!(
+                "Return EXACTLY {} consecutive OHLCV objects for the next {} periods.",
+                count, count
+            )
+        };
+        let response_wrapper = if self.wrapped {
+            fill(&self.pack.response_wrapper, &[("key", PREDICTIONS_KEY)])
+        } else {
+            String::new()
+        };
+        let data_format = self.data_format;
+        vec![
+            ("instructions", self.instructions.to_string()),
+            ("count", count.to_string()),
+            ("count_message", count_message),
+            ("periods", self.periods.to_string()),
+            ("data", self.data.to_string()),
+            ("indicators", self.indicators.to_string()),
+            ("gaps", self.gaps.to_string()),
+            (
+                "encoding_note",
+                self.encoding.prompt_note(self.pack).to_string(),
+            ),
+            ("example", data_format.example(count, self.pack)),
+            ("response_noun", data_format.response_noun().to_string()),
+            ("response_shape", data_format.response_shape()),
+            ("response_container", data_format.response_container()),
+            ("plain_name", data_format.plain_name().to_string()),
+            ("response_wrapper", response_wrapper),
+        ]
+    }
+}
+
+/// Renders the `predict_ohlcv` messages shared by all providers. `template`
+/// overrides the registered one and must only use `predict_ohlcv_variables`.
+/// Languages without a registered template use the English one.
+pub(crate) fn predict_ohlcv_messages(
+    template: Option<&PromptTemplate>,
+    inputs: &PredictInputs,
+) -> Result<Vec<ChatMessage>, TemplateError> {
+    let template = match template {
+        Some(template) => {
+            template.validate(&predict_ohlcv_variables())?;
+            template.clone()
+        }
+        None => get_template(&predict_template_name(inputs.language))
+            .or_else(|| get_template(PREDICT_OHLCV))
+            .unwrap_or_else(PromptTemplate::predict_ohlcv),
+    };
+    let variables = inputs.variables();
+    let variables: Vec<(&str, &str)> = variables
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect();
+    Ok(template.render(&variables))
+}
+
+/// The rejected `predict_ohlcv` reply and a request to correct it, appended to the
+/// conversation before asking again.
+pub(crate) fn repair_messages(
+    content: &str,
+    error: &str,
+    count: usize,
+    data_format: OhlcvFormat,
+) -> [ChatMessage; 2] {
+    [
+        ChatMessage {
+            role: ChatRole::Assistant,
+            content: content.to_string(),
+        },
+        ChatMessage {
+            role: ChatRole::User,
+            content: format!(
+                "Your previous reply was rejected: {}\nFix this and return EXACTLY {} OHLCV object(s) {} with no additional text.",
+                error,
+                count,
+                data_format.response_container()
+            ),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{indicators::IndicatorContext, types::OHLCV};
+
+    fn bars() -> Vec<OHLCV> {
+        (0..30)
+            .map(|i| {
+                let close = 100.0 + (i % 7) as f64 - (i % 3) as f64;
+                OHLCV {
+                    open: close - 0.5,
+                    high: close + 1.0,
+                    low: close - 1.0,
+                    close,
+                    volume: 1000.0 + 10.0 * i as f64,
+                }
+            })
+            .collect()
+    }
+
+    fn render(language: &Language) -> Vec<ChatMessage> {
+        let bars = bars();
+        let pack = crate::language::language_pack(language).unwrap();
+        let encoding = EncodingContext::new(PriceEncoding::PercentChange, &bars);
+        let indicators = IndicatorContext::default().prompt_note(&bars, &pack);
+        predict_ohlcv_messages(
+            None,
+            &PredictInputs {
+                instructions: "predict",
+                count: 3,
+                periods: bars.len(),
+                data: "[]",
+                data_format: OhlcvFormat::Json,
+                encoding: &encoding,
+                indicators: &indicators,
+                gaps: "",
+                wrapped: true,
+                language,
+                pack: &pack,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn chinese_predict_prompt_has_no_english_sections() {
+        let messages = render(&Language::SimplifiedChinese);
+        let (system, user) = (&messages[0].content, &messages[1].content);
+        assert!(system.contains("3个周期的有效响应示例"));
+        assert!(system.contains("其余1个OHLCV对象遵循相同格式"));
+        assert!(user.contains("所有价格均编码为相对最后收盘价的百分比变化"));
+        assert!(user.contains("基于历史数据计算的参考信息"));
+        assert!(user.contains("该数据集包含30个周期的OHLCV数据。"));
+        for english in [
+            "Example of valid response",
+            "All prices",
+            "Computed context",
+            "This dataset",
+        ] {
+            assert!(
+                !system.contains(english) && !user.contains(english),
+                "{}",
+                english
+            );
+        }
+    }
+
+    #[test]
+    fn wrapper_rule_follows_the_last_rule_in_both_languages() {
+        for (language, wrapper) in [
+            (
+                Language::English,
+                "9. Wrap the array in a JSON object: {\"predictions\": <array>}",
+            ),
+            (
+                Language::SimplifiedChinese,
+                "9. 将数组包装在JSON对象中：{\"predictions\": <数组>}",
+            ),
+        ] {
+            let system = &render(&language)[0].content;
+            assert!(system.contains("\n8. "), "{}", system);
+            assert!(system.contains(wrapper), "{}", system);
+        }
+    }
+}
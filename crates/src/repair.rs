@@ -0,0 +1,474 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    serialization::{FIELDS, PREDICTIONS_KEY, strip_code_fences},
+    types::OHLCV,
+};
+
+/// A quirk in a model reply that the lenient parser worked around.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// Markdown code fence lines were removed.
+    CodeFences,
+    /// Text before or after the JSON value was ignored.
+    SurroundingText { before: String, after: String },
+    /// Trailing commas before `]` or `}` were removed.
+    TrailingCommas,
+    /// The rows were read from this key of a wrapping object, e.g. `data`. The
+    /// `predictions` wrapper that JSON response formats ask for is not a repair.
+    Unwrapped(String),
+    /// A single object was read as a one-row array.
+    SingleObject,
+    /// A key was read as a field, e.g. `o` as `open`.
+    RenamedKey { key: String, field: &'static str },
+    /// A number given as a string was converted.
+    StringNumber { element: usize, field: &'static str },
+}
+
+impl Repair {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::CodeFences => "removed markdown code fences".to_string(),
+            Self::SurroundingText { before, after } => {
+                let mut parts = Vec::new();
+                if !before.is_empty() {
+                    parts.push(format!("{:?} before", excerpt(before)));
+                }
+                if !after.is_empty() {
+                    parts.push(format!("{:?} after", excerpt(after)));
+                }
+                format!("ignored text {} the data", parts.join(" and "))
+            }
+            Self::TrailingCommas => "removed trailing commas".to_string(),
+            Self::Unwrapped(key) => format!("read the rows from the '{}' key", key),
+            Self::SingleObject => "read a single object as a one-row array".to_string(),
+            Self::RenamedKey { key, field } => format!("read '{}' as '{}'", key, field),
+            Self::StringNumber { element, field } => {
+                format!(
+                    "converted string '{}' of element {} to a number",
+                    field, element
+                )
+            }
+        }
+    }
+}
+
+/// Bars read by `parse_ohlcv_lenient` and the repairs that were needed.
+#[derive(Debug, Clone)]
+pub struct LenientReply {
+    pub bars: Vec<OHLCV>,
+    pub repairs: Vec<Repair>,
+}
+
+impl LenientReply {
+    /// One-line summary of the repairs, e.g. for logs.
+    pub fn diagnostic(&self) -> String {
+        describe_repairs(&self.repairs)
+    }
+}
+
+/// Reads OHLCV bars from a model reply, tolerating code fences, prose around the
+/// data, wrapping objects such as `{"predictions": [...]}`, numbers as strings,
+/// trailing commas, `o/h/l/c/v` keys and `[open, high, low, close, volume]` rows.
+/// Bars are not validated; see `OhlcvFormat::parse_response`.
+pub fn parse_ohlcv_lenient(content: &str) -> Result<LenientReply, String> {
+    let (rows, repairs) = lenient_rows(content)?;
+    Ok(LenientReply {
+        bars: rows
+            .iter()
+            .map(|row| OHLCV {
+                open: row[0],
+                high: row[1],
+                low: row[2],
+                close: row[3],
+                volume: row[4],
+            })
+            .collect(),
+        repairs,
+    })
+}
+
+pub(crate) fn describe_repairs(repairs: &[Repair]) -> String {
+    if repairs.is_empty() {
+        "no repairs needed".to_string()
+    } else {
+        repairs
+            .iter()
+            .map(Repair::describe)
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// `[open, high, low, close, volume]` rows of a reply, for any JSON-based format.
+pub(crate) fn lenient_rows(content: &str) -> Result<(Vec<[f64; 5]>, Vec<Repair>), String> {
+    let mut repairs = Vec::new();
+    let text = if content
+        .lines()
+        .any(|line| line.trim_start().starts_with("```"))
+    {
+        repairs.push(Repair::CodeFences);
+        strip_code_fences(content)
+    } else {
+        content.to_string()
+    };
+
+    // Try each '[' or '{' in turn, parsing exactly one JSON value from it rather than
+    // matching up to the last bracket, so bracketed prose before or after is skipped.
+    let mut syntax_error = None;
+    let mut value_error = None;
+    let mut start = 0;
+    while let Some(offset) = text[start..].find(['[', '{']) {
+        let at = start + offset;
+        let (value, rest, fixed) = match parse_value(&text[at..]) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                syntax_error.get_or_insert(format!("Failed to parse JSON: {}", e));
+                start = at + 1;
+                continue;
+            }
+        };
+        let mut candidate = Vec::new();
+        match rows(&value, &mut candidate) {
+            Ok(rows) => {
+                let before = text[..at].trim();
+                let after = rest.trim();
+                if !before.is_empty() || !after.is_empty() {
+                    repairs.push(Repair::SurroundingText {
+                        before: before.to_string(),
+                        after: after.to_string(),
+                    });
+                }
+                if fixed {
+                    repairs.push(Repair::TrailingCommas);
+                }
+                repairs.extend(candidate);
+                return Ok((rows, repairs));
+            }
+            Err(e) => {
+                value_error.get_or_insert(e);
+                // Skip the whole value so its nested objects are not tried on their own.
+                start = if fixed {
+                    at + 1
+                } else {
+                    text.len() - rest.len()
+                };
+            }
+        }
+    }
+    Err(value_error
+        .or(syntax_error)
+        .unwrap_or_else(|| "No JSON array found in response".to_string()))
+}
+
+/// The first JSON value of `text` and the text after it. Trailing commas are removed
+/// (`true` in the result) when the value does not parse as is.
+fn parse_value(text: &str) -> Result<(Value, String, bool), serde_json::Error> {
+    let mut values = serde_json::Deserializer::from_str(text).into_iter::<Value>();
+    match values
+        .next()
+        .unwrap_or_else(|| serde_json::from_str::<Value>(text))
+    {
+        Ok(value) => Ok((value, text[values.byte_offset()..].to_string(), false)),
+        Err(e) => {
+            let fixed = remove_trailing_commas(text);
+            if fixed == text {
+                return Err(e);
+            }
+            let mut values = serde_json::Deserializer::from_str(&fixed).into_iter::<Value>();
+            match values.next() {
+                Some(Ok(value)) => Ok((value, fixed[values.byte_offset()..].to_string(), true)),
+                _ => Err(e),
+            }
+        }
+    }
+}
+
+/// Drops commas directly followed (after whitespace) by `]` or `}`, leaving string
+/// contents untouched.
+fn remove_trailing_commas(text: &str) -> String {
+    let mut fixed = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (at, c) in text.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' && text[at + 1..].trim_start().starts_with([']', '}']) {
+            continue;
+        }
+        fixed.push(c);
+    }
+    fixed
+}
+
+fn rows(value: &Value, repairs: &mut Vec<Repair>) -> Result<Vec<[f64; 5]>, String> {
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(obj) if field_keys(obj).iter().any(Option::is_some) => {
+            repairs.push(Repair::SingleObject);
+            return Ok(vec![row(value, 0, repairs)?]);
+        }
+        Value::Object(obj) => {
+            let (key, items) = obj
+                .iter()
+                .find_map(|(key, v)| match v {
+                    Value::Array(items)
+                        if items.iter().all(|item| item.is_object() || item.is_array()) =>
+                    {
+                        Some((key, items))
+                    }
+                    _ => None,
+                })
+                .ok_or_else(|| "No OHLCV array found in the JSON object".to_string())?;
+            if key != PREDICTIONS_KEY {
+                repairs.push(Repair::Unwrapped(key.clone()));
+            }
+            items
+        }
+        _ => return Err("No OHLCV array found in response".to_string()),
+    };
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| row(item, i, repairs))
+        .collect()
+}
+
+fn row(item: &Value, i: usize, repairs: &mut Vec<Repair>) -> Result<[f64; 5], String> {
+    let mut row = [0.0; 5];
+    match item {
+        Value::Object(obj) => {
+            for ((c, field), key) in FIELDS.iter().enumerate().zip(field_keys(obj)) {
+                let key = key
+                    .ok_or_else(|| format!("Element {} missing or invalid '{}' field", i, field))?;
+                if key != *field {
+                    let repair = Repair::RenamedKey {
+                        key: key.to_string(),
+                        field,
+                    };
+                    if !repairs.contains(&repair) {
+                        repairs.push(repair);
+                    }
+                }
+                row[c] = number(&obj[key], i, field, repairs)?;
+            }
+        }
+        Value::Array(values) if values.len() == 5 => {
+            for (c, (value, field)) in values.iter().zip(FIELDS).enumerate() {
+                row[c] = number(value, i, field, repairs)?;
+            }
+        }
+        Value::Array(_) => return Err(format!("Element {} must have exactly 5 values", i)),
+        _ => return Err(format!("Element {} is not an object", i)),
+    }
+    Ok(row)
+}
+
+/// Key used for each field, matching the full name or its abbreviation case-insensitively.
+fn field_keys(obj: &Map<String, Value>) -> [Option<&str>; 5] {
+    FIELDS.map(|field| {
+        if obj.contains_key(field) {
+            return Some(field);
+        }
+        obj.keys()
+            .find(|key| {
+                let key = key.to_lowercase();
+                key == field || key == field[..1] || (field == "volume" && key == "vol")
+            })
+            .map(String::as_str)
+    })
+}
+
+fn number(
+    value: &Value,
+    i: usize,
+    field: &'static str,
+    repairs: &mut Vec<Repair>,
+) -> Result<f64, String> {
+    if let Some(n) = value.as_f64() {
+        return Ok(n);
+    }
+    match value.as_str().and_then(parse_number_text) {
+        Some(n) if n.is_finite() => {
+            repairs.push(Repair::StringNumber { element: i, field });
+            Ok(n)
+        }
+        _ => Err(format!(
+            "Element {} missing or invalid '{}' field",
+            i, field
+        )),
+    }
+}
+
+/// Parses a number given as text. Commas are only accepted as thousands separators,
+/// e.g. `1,234.5`; anything else such as a decimal comma is rejected.
+fn parse_number_text(text: &str) -> Option<f64> {
+    let text = text.trim();
+    if text.contains(',') {
+        let grouped = regex::Regex::new(r"^[+-]?\d{1,3}(,\d{3})+(\.\d+)?$").unwrap();
+        if !grouped.is_match(text) {
+            return None;
+        }
+        return text.replace(',', "").parse().ok();
+    }
+    text.parse().ok()
+}
+
+fn excerpt(text: &str) -> String {
+    const MAX_CHARS: usize = 40;
+    if text.chars().count() > MAX_CHARS {
+        format!("{}...", text.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROW: &str = r#"{"open": 1.0, "high": 2.0, "low": 0.5, "close": 1.5, "volume": 100}"#;
+
+    fn parse(content: &str) -> LenientReply {
+        parse_ohlcv_lenient(content).unwrap()
+    }
+
+    fn assert_bar(bar: &OHLCV) {
+        assert_eq!(
+            (bar.open, bar.high, bar.low, bar.close, bar.volume),
+            (1.0, 2.0, 0.5, 1.5, 100.0)
+        );
+    }
+
+    #[test]
+    fn clean_array_needs_no_repairs() {
+        let reply = parse(&format!("[{}]", ROW));
+        assert_eq!(reply.bars.len(), 1);
+        assert_bar(&reply.bars[0]);
+        assert!(reply.repairs.is_empty());
+    }
+
+    #[test]
+    fn code_fences() {
+        let reply = parse(&format!("```json\n[{}]\n```", ROW));
+        assert_bar(&reply.bars[0]);
+        assert_eq!(reply.repairs, vec![Repair::CodeFences]);
+    }
+
+    #[test]
+    fn surrounding_text() {
+        let reply = parse(&format!("Here [are] the bars: [{}] Done.", ROW));
+        assert_bar(&reply.bars[0]);
+        assert_eq!(
+            reply.repairs,
+            vec![Repair::SurroundingText {
+                before: "Here [are] the bars:".to_string(),
+                after: "Done.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let reply = parse(r#"[{"open": 1, "high": 2, "low": 0.5, "close": 1.5, "volume": 100,},]"#);
+        assert_bar(&reply.bars[0]);
+        assert_eq!(reply.repairs, vec![Repair::TrailingCommas]);
+    }
+
+    #[test]
+    fn trailing_comma_repair_leaves_strings_alone() {
+        assert_eq!(
+            remove_trailing_commas(r#"{"note": "a, ]", "list": [1, 2,],}"#),
+            r#"{"note": "a, ]", "list": [1, 2]}"#
+        );
+        assert_eq!(
+            remove_trailing_commas(r#"["escaped \" ,}", 1,]"#),
+            r#"["escaped \" ,}", 1]"#
+        );
+    }
+
+    #[test]
+    fn unwrapped() {
+        let reply = parse(&format!(r#"{{"data": [{}]}}"#, ROW));
+        assert_bar(&reply.bars[0]);
+        assert_eq!(reply.repairs, vec![Repair::Unwrapped("data".to_string())]);
+    }
+
+    #[test]
+    fn predictions_wrapper_is_not_a_repair() {
+        let reply = parse(&format!(r#"{{"predictions": [{}]}}"#, ROW));
+        assert_bar(&reply.bars[0]);
+        assert!(reply.repairs.is_empty());
+    }
+
+    #[test]
+    fn single_object() {
+        let reply = parse(ROW);
+        assert_bar(&reply.bars[0]);
+        assert_eq!(reply.repairs, vec![Repair::SingleObject]);
+    }
+
+    #[test]
+    fn renamed_keys() {
+        let reply = parse(r#"[{"o": 1, "h": 2, "l": 0.5, "c": 1.5, "vol": 100}]"#);
+        assert_bar(&reply.bars[0]);
+        assert_eq!(reply.repairs.len(), 5);
+        assert!(reply.repairs.contains(&Repair::RenamedKey {
+            key: "vol".to_string(),
+            field: "volume",
+        }));
+    }
+
+    #[test]
+    fn string_numbers() {
+        let reply =
+            parse(r#"[{"open": "1", "high": 2, "low": 0.5, "close": 1.5, "volume": "100"}]"#);
+        assert_bar(&reply.bars[0]);
+        assert_eq!(
+            reply.repairs,
+            vec![
+                Repair::StringNumber {
+                    element: 0,
+                    field: "open"
+                },
+                Repair::StringNumber {
+                    element: 0,
+                    field: "volume"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn string_numbers_accept_only_thousands_separators() {
+        assert_eq!(parse_number_text("1,234,567.5"), Some(1_234_567.5));
+        assert_eq!(parse_number_text(" -1,000 "), Some(-1000.0));
+        assert_eq!(parse_number_text("1,5"), None);
+        assert_eq!(parse_number_text("12,34"), None);
+        assert_eq!(parse_number_text("1234,567"), None);
+        assert!(
+            parse_ohlcv_lenient(
+                r#"[{"open": "1,5", "high": 2, "low": 0.5, "close": 1.5, "volume": 100}]"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn compact_rows() {
+        let reply = parse("[[1, 2, 0.5, 1.5, 100], [1.5, 2.5, 1, 2, 200]]");
+        assert_eq!(reply.bars.len(), 2);
+        assert_bar(&reply.bars[0]);
+        assert_eq!(reply.bars[1].close, 2.0);
+        assert!(reply.repairs.is_empty());
+        assert!(parse_ohlcv_lenient("[[1, 2, 0.5, 1.5]]").is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Candle, Interval, OHLCV};
+
+const DAY_MILLIS: i64 = 86_400_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// Days since the previous Monday.
+    fn days_from_monday(&self) -> i64 {
+        match self {
+            Self::Monday => 0,
+            Self::Tuesday => 1,
+            Self::Wednesday => 2,
+            Self::Thursday => 3,
+            Self::Friday => 4,
+            Self::Saturday => 5,
+            Self::Sunday => 6,
+        }
+    }
+}
+
+/// Where bucket boundaries are anchored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Alignment {
+    /// Buckets start at multiples of the interval since the Unix epoch; weeks start on Monday.
+    Utc,
+    /// Buckets start at the exchange session open, given as an offset from UTC midnight.
+    Session { open_offset_millis: i64 },
+    /// Like `Utc`, but weekly buckets start on the given day.
+    WeekStart(Weekday),
+}
+
+impl Alignment {
+    fn origin_millis(&self, target: Interval) -> i64 {
+        // 1970-01-01 was a Thursday, so the first Monday is four days later.
+        let monday = 4 * DAY_MILLIS;
+        match self {
+            Self::Utc if target == Interval::W1 => monday,
+            Self::Utc => 0,
+            Self::Session { open_offset_millis } => *open_offset_millis,
+            Self::WeekStart(day) => monday + day.days_from_monday() * DAY_MILLIS,
+        }
+    }
+}
+
+/// What to do with a trailing bucket that does not yet cover its whole interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PartialBar {
+    Keep,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ResampleOptions {
+    pub alignment: Alignment,
+    pub partial: PartialBar,
+}
+
+impl Default for ResampleOptions {
+    fn default() -> Self {
+        Self {
+            alignment: Alignment::Utc,
+            partial: PartialBar::Keep,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResampleError {
+    #[error("Invalid interval: {0}")]
+    InvalidInterval(String),
+    #[error("Candles must be in ascending timestamp order (index {0})")]
+    Unsorted(usize),
+}
+
+/// Aggregates timestamped candles into `target` buckets:
+/// first open, max high, min low, last close and summed volume.
+pub fn resample_candles(
+    candles: &[Candle],
+    target: Interval,
+    options: ResampleOptions,
+) -> Result<Vec<Candle>, ResampleError> {
+    if candles.is_empty() {
+        return Ok(Vec::new());
+    }
+    if let Some(i) = candles
+        .windows(2)
+        .position(|pair| pair[1].timestamp < pair[0].timestamp)
+    {
+        return Err(ResampleError::Unsorted(i + 1));
+    }
+    let target_millis = target.as_millis();
+    let source_millis = Candle::series_step_millis(candles).unwrap_or(target_millis);
+    if source_millis > target_millis {
+        return Err(ResampleError::InvalidInterval(format!(
+            "cannot resample {}ms bars into finer {} bars",
+            source_millis,
+            target.as_str()
+        )));
+    }
+    let origin = options.alignment.origin_millis(target);
+    let bucket_of = |timestamp: i64| -> i64 {
+        origin + (timestamp - origin).div_euclid(target_millis) * target_millis
+    };
+    let mut result: Vec<Candle> = Vec::new();
+    for candle in candles {
+        let bucket = bucket_of(candle.timestamp);
+        match result.last_mut() {
+            Some(current) if current.timestamp == bucket => {
+                current.high = current.high.max(candle.high);
+                current.low = current.low.min(candle.low);
+                current.close = candle.close;
+                current.volume += candle.volume;
+            }
+            _ => result.push(Candle {
+                timestamp: bucket,
+                interval: Some(target),
+                ..candle.clone()
+            }),
+        }
+    }
+    if options.partial == PartialBar::Drop {
+        let last = &candles[candles.len() - 1];
+        let covered_until = last.timestamp + source_millis;
+        if result
+            .last()
+            .is_some_and(|bucket| covered_until < bucket.timestamp + target_millis)
+        {
+            result.pop();
+        }
+    }
+    Ok(result)
+}
+
+/// Aggregates untimestamped bars in groups of `factor` consecutive bars.
+pub fn resample_ohlcv(ohlcv_array: &[OHLCV], factor: usize, partial: PartialBar) -> Vec<OHLCV> {
+    if factor == 0 {
+        return Vec::new();
+    }
+    ohlcv_array
+        .chunks(factor)
+        .filter(|chunk| partial == PartialBar::Keep || chunk.len() == factor)
+        .map(|chunk| OHLCV {
+            open: chunk[0].open,
+            high: chunk.iter().map(|b| b.high).fold(f64::MIN, f64::max),
+            low: chunk.iter().map(|b| b.low).fold(f64::MAX, f64::min),
+            close: chunk[chunk.len() - 1].close,
+            volume: chunk.iter().map(|b| b.volume).sum(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000;
+    /// Monday 2024-01-01T00:00:00Z.
+    const MONDAY: i64 = 1_704_067_200_000;
+
+    fn candles(start: i64, step: i64, count: usize) -> Vec<Candle> {
+        (0..count)
+            .map(|i| {
+                let close = 10.0 + i as f64;
+                Candle {
+                    interval: Interval::from_millis(step),
+                    ..Candle::from_ohlcv(
+                        start + step * i as i64,
+                        &OHLCV {
+                            open: close - 0.5,
+                            high: close + 1.0,
+                            low: close - 1.0,
+                            close,
+                            volume: 10.0,
+                        },
+                    )
+                }
+            })
+            .collect()
+    }
+
+    fn timestamps(candles: &[Candle]) -> Vec<i64> {
+        candles.iter().map(|candle| candle.timestamp).collect()
+    }
+
+    #[test]
+    fn aggregates_minutes_into_epoch_aligned_buckets() {
+        // 00:03 to 00:09 falls into the 00:00 and 00:05 buckets.
+        let bars = candles(3 * MINUTE, MINUTE, 7);
+        let resampled = resample_candles(&bars, Interval::M5, ResampleOptions::default()).unwrap();
+        assert_eq!(timestamps(&resampled), vec![0, 5 * MINUTE]);
+        let first = &resampled[0];
+        assert_eq!(
+            (first.open, first.high, first.low, first.close, first.volume),
+            (9.5, 12.0, 9.0, 11.0, 20.0)
+        );
+        assert_eq!(resampled[1].volume, 50.0);
+        assert_eq!(resampled[1].interval, Some(Interval::M5));
+    }
+
+    #[test]
+    fn weeks_start_on_monday_unless_configured() {
+        // Wednesday 2024-01-03 to Tuesday 2024-01-09.
+        let days = candles(MONDAY + 2 * DAY_MILLIS, DAY_MILLIS, 7);
+        let utc = resample_candles(&days, Interval::W1, ResampleOptions::default()).unwrap();
+        assert_eq!(timestamps(&utc), vec![MONDAY, MONDAY + 7 * DAY_MILLIS]);
+        assert_eq!(utc[0].volume, 50.0);
+
+        let sunday = ResampleOptions {
+            alignment: Alignment::WeekStart(Weekday::Sunday),
+            ..Default::default()
+        };
+        let weeks = resample_candles(&days, Interval::W1, sunday).unwrap();
+        assert_eq!(
+            timestamps(&weeks),
+            vec![MONDAY - DAY_MILLIS, MONDAY + 6 * DAY_MILLIS]
+        );
+    }
+
+    #[test]
+    fn session_alignment_starts_days_at_the_open() {
+        let hour = 60 * MINUTE;
+        let open = 9 * hour + 30 * MINUTE;
+        let hours = candles(MONDAY, hour, 24);
+        let options = ResampleOptions {
+            alignment: Alignment::Session {
+                open_offset_millis: open,
+            },
+            ..Default::default()
+        };
+        let days = resample_candles(&hours, Interval::D1, options).unwrap();
+        assert_eq!(
+            timestamps(&days),
+            vec![MONDAY - DAY_MILLIS + open, MONDAY + open]
+        );
+        // 00:00 to 09:00 belong to the previous session.
+        assert_eq!(days[0].volume, 100.0);
+    }
+
+    #[test]
+    fn drops_an_incomplete_trailing_bucket_on_request() {
+        let bars = candles(0, MINUTE, 12);
+        let options = ResampleOptions {
+            partial: PartialBar::Drop,
+            ..Default::default()
+        };
+        let resampled = resample_candles(&bars, Interval::M5, options).unwrap();
+        assert_eq!(timestamps(&resampled), vec![0, 5 * MINUTE]);
+        let kept = resample_candles(&bars, Interval::M5, ResampleOptions::default()).unwrap();
+        assert_eq!(kept.len(), 3);
+    }
+
+    #[test]
+    fn rejects_unsorted_and_finer_targets() {
+        let mut bars = candles(0, 5 * MINUTE, 3);
+        assert!(matches!(
+            resample_candles(&bars, Interval::M1, ResampleOptions::default()),
+            Err(ResampleError::InvalidInterval(_))
+        ));
+        bars.swap(1, 2);
+        assert!(matches!(
+            resample_candles(&bars, Interval::M15, ResampleOptions::default()),
+            Err(ResampleError::Unsorted(2))
+        ));
+    }
+
+    #[test]
+    fn groups_untimestamped_bars_by_factor() {
+        let bars: Vec<OHLCV> = candles(0, MINUTE, 5).iter().map(OHLCV::from).collect();
+        let grouped = resample_ohlcv(&bars, 2, PartialBar::Keep);
+        assert_eq!(grouped.len(), 3);
+        assert_eq!((grouped[0].open, grouped[0].close), (9.5, 11.0));
+        assert_eq!(grouped[2].volume, 10.0);
+        assert_eq!(resample_ohlcv(&bars, 2, PartialBar::Drop).len(), 2);
+        assert!(resample_ohlcv(&bars, 0, PartialBar::Keep).is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+use serde::Serialize;
+use std::{future::Future, sync::Arc, time::Duration};
+
+use crate::{
+    aliyun::{self, AliyunAI},
+    deepseek::{self, DeepSeekAI},
+    language::Language,
+    serialization::OhlcvFormat,
+    types::{ChatMessage, ErrorKind, ModelId, OHLCV, ResponseFormat},
+};
+
+/// A provider client a route target can dispatch to.
+#[derive(Clone)]
+pub enum ProviderClient {
+    Aliyun(Arc<AliyunAI>),
+    DeepSeek(Arc<DeepSeekAI>),
+}
+
+impl ProviderClient {
+    pub(crate) async fn predict_ohlcv(
+        &self,
+        model: ModelId,
+        ohlcv_array: &[OHLCV],
+        instructions: Option<&str>,
+        count: Option<usize>,
+        opts: RouteOptions,
+    ) -> Result<Vec<OHLCV>, (ErrorKind, String)> {
+        match (self, model) {
+            (Self::Aliyun(client), ModelId::Aliyun(model_type)) => client
+                .predict_ohlcv(
+                    ohlcv_array,
+                    instructions,
+                    count,
+                    Some(aliyun_options(&opts, model_type)),
+                )
+                .await
+                .map_err(|e| (e.kind(), e.to_string())),
+            (Self::DeepSeek(client), ModelId::DeepSeek(model_type)) => client
+                .predict_ohlcv(
+                    ohlcv_array,
+                    instructions,
+                    count,
+                    Some(deepseek_options(&opts, model_type)),
+                )
+                .await
+                .map_err(|e| (e.kind(), e.to_string())),
+            _ => Err(provider_mismatch()),
+        }
+    }
+}
+
+/// One entry of the fallback chain.
+#[derive(Clone)]
+pub struct RouteTarget {
+    pub client: ProviderClient,
+    pub model: ModelId,
+    /// Per-target timeout; requests exceeding it fail with `ErrorKind::Timeout`.
+    pub timeout: Option<Duration>,
+    /// Error kinds that make the router fall through to the next target.
+    pub fallback_on: Vec<ErrorKind>,
+}
+
+impl RouteTarget {
+    pub fn aliyun(client: Arc<AliyunAI>, model_type: aliyun::AliYunModelType) -> Self {
+        Self {
+            client: ProviderClient::Aliyun(client),
+            model: ModelId::Aliyun(model_type),
+            timeout: None,
+            fallback_on: default_fallback_kinds(),
+        }
+    }
+
+    pub fn deepseek(client: Arc<DeepSeekAI>, model_type: deepseek::DeepSeekModelType) -> Self {
+        Self {
+            client: ProviderClient::DeepSeek(client),
+            model: ModelId::DeepSeek(model_type),
+            timeout: None,
+            fallback_on: default_fallback_kinds(),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_fallback_on(mut self, kinds: &[ErrorKind]) -> Self {
+        self.fallback_on = kinds.to_vec();
+        self
+    }
+}
+
+/// Transient failures worth retrying elsewhere: throttling, timeouts and transport errors.
+///
+/// `ErrorKind::Api` is left out since it also covers 4xx replies, such as a malformed
+/// request, that the next target would reject too; opt in with `with_fallback_on`.
+pub fn default_fallback_kinds() -> Vec<ErrorKind> {
+    vec![ErrorKind::RateLimit, ErrorKind::Timeout, ErrorKind::Network]
+}
+
+/// Provider-independent request options, translated into each provider's `ChatOptions`.
+/// `None` keeps the provider's default.
+#[derive(Debug, Clone, Default)]
+pub struct RouteOptions {
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub language: Option<Language>,
+    /// Sent to every target; one whose model can't honour it fails with `ErrorKind::Config`.
+    /// Leave it `None` to let `predict_ohlcv` pick the strictest format per model.
+    pub response_format: Option<ResponseFormat>,
+    pub data_format: Option<OhlcvFormat>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteFailure {
+    pub target: ModelId,
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+/// A successful response together with the target that served it.
+#[derive(Debug, Clone)]
+pub struct Routed<T> {
+    pub value: T,
+    pub served_by: ModelId,
+    pub target_index: usize,
+    /// Failures of earlier targets that were skipped over.
+    pub failures: Vec<RouteFailure>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouterError {
+    #[error("Invalid configuration: {0}")]
+    ConfigError(String),
+    #[error("Target {} failed with non-fallback error: {}", .0.target.as_str(), .0.message)]
+    TargetFailed(RouteFailure),
+    #[error("All {} targets failed", .0.len())]
+    AllTargetsFailed(Vec<RouteFailure>),
+}
+
+/// Sends requests to an ordered list of targets, falling through on configured error kinds.
+#[derive(Clone, Default)]
+pub struct Router {
+    targets: Vec<RouteTarget>,
+}
+
+impl Router {
+    pub fn new(targets: Vec<RouteTarget>) -> Self {
+        Self { targets }
+    }
+
+    pub fn add_target(&mut self, target: RouteTarget) {
+        self.targets.push(target);
+    }
+
+    pub fn targets(&self) -> &[RouteTarget] {
+        &self.targets
+    }
+
+    pub async fn chat_completion(
+        &self,
+        messages: &[ChatMessage],
+        options: Option<RouteOptions>,
+    ) -> Result<Routed<serde_json::Value>, RouterError> {
+        let opts = options.unwrap_or_default();
+        self.route(|target| {
+            let opts = opts.clone();
+            async move {
+                match (&target.client, target.model) {
+                    (ProviderClient::Aliyun(client), ModelId::Aliyun(model_type)) => client
+                        .chat_completion(messages, Some(aliyun_options(&opts, model_type)))
+                        .await
+                        .map_err(|e| (e.kind(), e.to_string())),
+                    (ProviderClient::DeepSeek(client), ModelId::DeepSeek(model_type)) => client
+                        .chat_completion(messages, Some(deepseek_options(&opts, model_type)))
+                        .await
+                        .map_err(|e| (e.kind(), e.to_string())),
+                    _ => Err(provider_mismatch()),
+                }
+            }
+        })
+        .await
+    }
+
+    pub async fn predict_ohlcv(
+        &self,
+        ohlcv_array: &[OHLCV],
+        instructions: Option<&str>,
+        count: Option<usize>,
+        options: Option<RouteOptions>,
+    ) -> Result<Routed<Vec<OHLCV>>, RouterError> {
+        let opts = options.unwrap_or_default();
+        self.route(|target| {
+            target.client.predict_ohlcv(
+                target.model,
+                ohlcv_array,
+                instructions,
+                count,
+                opts.clone(),
+            )
+        })
+        .await
+    }
+
+    async fn route<'a, T, F, Fut>(&'a self, mut call: F) -> Result<Routed<T>, RouterError>
+    where
+        F: FnMut(&'a RouteTarget) -> Fut,
+        Fut: Future<Output = Result<T, (ErrorKind, String)>>,
+    {
+        if self.targets.is_empty() {
+            return Err(RouterError::ConfigError(
+                "Router has no targets".to_string(),
+            ));
+        }
+        let mut failures = Vec::new();
+        for (index, target) in self.targets.iter().enumerate() {
+            let result = match target.timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, call(target)).await {
+                    Ok(result) => result,
+                    Err(_) => Err((
+                        ErrorKind::Timeout,
+                        format!("Request timed out after {:?}", timeout),
+                    )),
+                },
+                None => call(target).await,
+            };
+            match result {
+                Ok(value) => {
+                    return Ok(Routed {
+                        value,
+                        served_by: target.model,
+                        target_index: index,
+                        failures,
+                    });
+                }
+                Err((kind, message)) => {
+                    let failure = RouteFailure {
+                        target: target.model,
+                        kind,
+                        message,
+                    };
+                    if !target.fallback_on.contains(&kind) {
+                        return Err(RouterError::TargetFailed(failure));
+                    }
+                    failures.push(failure);
+                }
+            }
+        }
+        Err(RouterError::AllTargetsFailed(failures))
+    }
+}
+
+fn provider_mismatch() -> (ErrorKind, String) {
+    (
+        ErrorKind::Config,
+        "Target model does not belong to the target client's provider".to_string(),
+    )
+}
+
+fn aliyun_options(opts: &RouteOptions, model_type: aliyun::AliYunModelType) -> aliyun::ChatOptions {
+    let defaults = aliyun::ChatOptions::default();
+    aliyun::ChatOptions {
+        temperature: opts.temperature.or(defaults.temperature),
+        max_tokens: opts.max_tokens.or(defaults.max_tokens),
+        model_type: Some(model_type),
+        language: opts.language.clone().or(defaults.language),
+        response_format: opts.response_format.clone().or(defaults.response_format),
+        data_format: opts.data_format.unwrap_or(defaults.data_format),
+        ..defaults
+    }
+}
+
+fn deepseek_options(
+    opts: &RouteOptions,
+    model_type: deepseek::DeepSeekModelType,
+) -> deepseek::ChatOptions {
+    let defaults = deepseek::ChatOptions::default();
+    deepseek::ChatOptions {
+        temperature: opts.temperature.or(defaults.temperature),
+        max_tokens: opts.max_tokens.or(defaults.max_tokens),
+        model_type: Some(model_type),
+        language: opts.language.clone().or(defaults.language),
+        response_format: opts.response_format.clone().or(defaults.response_format),
+        data_format: opts.data_format.unwrap_or(defaults.data_format),
+        ..defaults
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_errors_do_not_fall_through_by_default() {
+        let kinds = default_fallback_kinds();
+        assert!(!kinds.contains(&ErrorKind::Api));
+        assert!(kinds.contains(&ErrorKind::RateLimit));
+    }
+
+    #[test]
+    fn route_options_reach_both_providers() {
+        let opts = RouteOptions {
+            temperature: Some(0.1),
+            language: Some(Language::SimplifiedChinese),
+            response_format: Some(ResponseFormat::JsonObject),
+            data_format: Some(OhlcvFormat::Csv),
+            ..Default::default()
+        };
+        let aliyun = aliyun_options(&opts, aliyun::AliYunModelType::QWEN_PLUS);
+        assert_eq!(aliyun.temperature, Some(0.1));
+        assert_eq!(aliyun.language, Some(Language::SimplifiedChinese));
+        assert_eq!(aliyun.response_format, Some(ResponseFormat::JsonObject));
+        assert_eq!(aliyun.data_format, OhlcvFormat::Csv);
+        let deepseek = deepseek_options(&opts, deepseek::DeepSeekModelType::DeepSeekChat);
+        assert_eq!(deepseek.language, Some(Language::SimplifiedChinese));
+        assert_eq!(deepseek.response_format, Some(ResponseFormat::JsonObject));
+        assert_eq!(deepseek.data_format, OhlcvFormat::Csv);
+        assert_eq!(
+            deepseek.model_type,
+            Some(deepseek::DeepSeekModelType::DeepSeekChat)
+        );
+    }
+
+    #[test]
+    fn unset_route_options_keep_provider_defaults() {
+        let deepseek = deepseek_options(
+            &RouteOptions::default(),
+            deepseek::DeepSeekModelType::DeepSeekChat,
+        );
+        let defaults = deepseek::ChatOptions::default();
+        assert_eq!(deepseek.temperature, defaults.temperature);
+        assert_eq!(deepseek.language, defaults.language);
+        assert_eq!(deepseek.response_format, defaults.response_format);
+        assert_eq!(deepseek.data_format, defaults.data_format);
+    }
+}
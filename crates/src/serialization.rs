@@ -0,0 +1,565 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    capability::Capability,
+    language::{LanguagePack, fill},
+    repair::{describe_repairs, lenient_rows},
+    types::{OHLCV, ResponseFormat},
+    validation::{Severity, bar_issues},
+};
+
+pub(crate) const FIELDS: [&str; 5] = ["open", "high", "low", "close", "volume"];
+
+/// Key of the object wrapping the rows when JSON mode forces an object reply.
+pub const PREDICTIONS_KEY: &str = "predictions";
+
+/// How OHLCV rows are written into prompts and expected back in replies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OhlcvFormat {
+    /// Pretty-printed array of `{open, high, low, close, volume}` objects.
+    #[default]
+    Json,
+    /// `[[open, high, low, close, volume], ...]` without whitespace.
+    CompactJson,
+    /// Comma-separated table with a header row.
+    Csv,
+    /// Space-aligned columns rounded to a fixed number of decimals.
+    FixedPrecision { decimals: u8 },
+    /// Compact arrays where every row after the first holds the change from the previous row.
+    Delta,
+}
+
+/// Size of one OHLCV series rendered in a given format.
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatStats {
+    pub format: OhlcvFormat,
+    pub chars: usize,
+    pub estimated_tokens: usize,
+    /// Fraction of estimated tokens saved relative to `OhlcvFormat::Json`.
+    pub savings: f64,
+}
+
+impl OhlcvFormat {
+    pub const BUILT_IN: [OhlcvFormat; 5] = [
+        Self::Json,
+        Self::CompactJson,
+        Self::Csv,
+        Self::FixedPrecision { decimals: 2 },
+        Self::Delta,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::CompactJson => "compact-json",
+            Self::Csv => "csv",
+            Self::FixedPrecision { .. } => "fixed-precision",
+            Self::Delta => "delta",
+        }
+    }
+
+    pub fn serialize(&self, ohlcv_array: &[OHLCV]) -> Result<String, String> {
+        match self {
+            Self::Json => serde_json::to_string_pretty(ohlcv_array).map_err(|e| e.to_string()),
+            Self::CompactJson => Ok(compact_rows(ohlcv_array.iter().map(values))),
+            Self::Delta => {
+                let rows = ohlcv_array.iter().enumerate().map(|(i, bar)| {
+                    if i == 0 {
+                        values(bar)
+                    } else {
+                        delta(&ohlcv_array[i - 1], bar)
+                    }
+                });
+                Ok(compact_rows(rows))
+            }
+            Self::Csv => {
+                let mut out = FIELDS.join(",");
+                for bar in ohlcv_array {
+                    out.push('\n');
+                    out.push_str(&values(bar).map(number).join(","));
+                }
+                Ok(out)
+            }
+            Self::FixedPrecision { decimals } => {
+                let decimals = *decimals as usize;
+                let cells: Vec<[String; 5]> = ohlcv_array
+                    .iter()
+                    .map(|bar| values(bar).map(|v| format!("{:.*}", decimals, v)))
+                    .collect();
+                let widths: Vec<usize> = (0..5)
+                    .map(|c| {
+                        cells
+                            .iter()
+                            .map(|row| row[c].len())
+                            .chain([FIELDS[c].len()])
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect();
+                let line = |row: [&str; 5]| {
+                    row.iter()
+                        .zip(&widths)
+                        .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+                let mut lines = vec![line(FIELDS)];
+                for row in &cells {
+                    lines.push(line([&row[0], &row[1], &row[2], &row[3], &row[4]]));
+                }
+                Ok(lines.join("\n"))
+            }
+        }
+    }
+
+    /// Noun phrase for what the model must return, e.g. "a JSON array of OHLCV objects".
+    pub fn response_noun(&self) -> &'static str {
+        match self {
+            Self::Json => "a JSON array of OHLCV objects",
+            Self::CompactJson => "a JSON array of [open, high, low, close, volume] arrays",
+            Self::Csv => "a CSV table with the header open,high,low,close,volume",
+            Self::FixedPrecision { .. } => {
+                "a table of space-separated columns with the header open high low close volume"
+            }
+            Self::Delta => "a JSON array of [open, high, low, close, volume] change arrays",
+        }
+    }
+
+    /// Tail of the user message reminder, e.g. "in a JSON array".
+    pub fn response_container(&self) -> String {
+        match self {
+            Self::Json => "in a JSON array".to_string(),
+            _ => format!("as {}", self.response_noun()),
+        }
+    }
+
+    /// Shape of the expected response, e.g. `[{open, high, low, close, volume}, ...]`.
+    pub fn response_shape(&self) -> String {
+        match self {
+            Self::Json => "[{open, high, low, close, volume}, ...]".to_string(),
+            Self::CompactJson => "[[open, high, low, close, volume], ...]".to_string(),
+            Self::Csv => "a header line open,high,low,close,volume followed by one row per period"
+                .to_string(),
+            Self::FixedPrecision { decimals } => format!(
+                "a header line open high low close volume followed by one row per period, {} decimals",
+                decimals
+            ),
+            Self::Delta => "[[d_open, d_high, d_low, d_close, d_volume], ...] where every row is the change from the previous period and the first row is the change from the last historical period".to_string(),
+        }
+    }
+
+    /// What "only" the reply may contain, e.g. "pure JSON".
+    pub fn plain_name(&self) -> &'static str {
+        match self {
+            Self::Json | Self::CompactJson | Self::Delta => "pure JSON",
+            Self::Csv => "plain CSV",
+            Self::FixedPrecision { .. } => "the plain table",
+        }
+    }
+
+    /// Whether replies in this format are JSON and can be constrained with `response_format`.
+    pub fn is_json(&self) -> bool {
+        matches!(self, Self::Json | Self::CompactJson | Self::Delta)
+    }
+
+    /// Schema of a reply of exactly `count` rows wrapped as `{"predictions": [...]}`,
+    /// or `None` for text formats.
+    pub fn response_schema(&self, count: usize) -> Option<Value> {
+        let row = match self {
+            Self::Json => json!({
+                "type": "object",
+                "properties": FIELDS
+                    .iter()
+                    .map(|field| (field.to_string(), json!({"type": "number"})))
+                    .collect::<serde_json::Map<_, _>>(),
+                "required": FIELDS,
+                "additionalProperties": false,
+            }),
+            Self::CompactJson | Self::Delta => json!({
+                "type": "array",
+                "items": {"type": "number"},
+                "minItems": 5,
+                "maxItems": 5,
+            }),
+            Self::Csv | Self::FixedPrecision { .. } => return None,
+        };
+        Some(json!({
+            "type": "object",
+            "properties": {
+                PREDICTIONS_KEY: {
+                    "type": "array",
+                    "items": row,
+                    "minItems": count,
+                    "maxItems": count,
+                },
+            },
+            "required": [PREDICTIONS_KEY],
+            "additionalProperties": false,
+        }))
+    }
+
+    /// Strictest `response_format` a model with `capabilities` accepts for a reply of
+    /// `count` rows: the schema, plain JSON mode, or `None` for text formats.
+    pub fn response_format(
+        &self,
+        count: usize,
+        capabilities: &[Capability],
+    ) -> Option<ResponseFormat> {
+        let schema = self.response_schema(count)?;
+        if capabilities.contains(&Capability::JsonSchema) {
+            Some(ResponseFormat::JsonSchema {
+                name: "ohlcv_predictions".to_string(),
+                schema,
+                strict: false,
+            })
+        } else if capabilities.contains(&Capability::JsonMode) {
+            Some(ResponseFormat::JsonObject)
+        } else {
+            None
+        }
+    }
+
+    /// Example of a valid response for `count` periods, introduced in the language of `pack`.
+    pub fn example(&self, count: usize, pack: &LanguagePack) -> String {
+        let first = OHLCV {
+            open: 115.5,
+            high: 118.0,
+            low: 114.0,
+            close: 117.0,
+            volume: 1350000.0,
+        };
+        let second = OHLCV {
+            open: 117.5,
+            high: 120.0,
+            low: 116.0,
+            close: 119.0,
+            volume: 1400000.0,
+        };
+        let count_text = count.to_string();
+        let more = count.saturating_sub(2).to_string();
+        if *self == Self::Json {
+            return if count == 1 {
+                format!(
+                    r#"{}
+[{{"open": 115.5, "high": 118.0, "low": 114.0, "close": 117.0, "volume": 1350000}}]"#,
+                    pack.example_single
+                )
+            } else {
+                let rest = if count > 2 {
+                    format!("\n  {}", fill(&pack.example_more, &[("more", &more)]))
+                } else {
+                    String::new()
+                };
+                format!(
+                    r#"{}
+[
+  {{"open": 115.5, "high": 118.0, "low": 114.0, "close": 117.0, "volume": 1350000}},
+  {{"open": 117.5, "high": 120.0, "low": 116.0, "close": 119.0, "volume": 1400000}}{}
+]"#,
+                    fill(&pack.example_multiple, &[("count", &count_text)]),
+                    rest
+                )
+            };
+        }
+        let sample = if count == 1 {
+            vec![first]
+        } else {
+            vec![first, second]
+        };
+        let body = if *self == Self::Delta {
+            // Reply rows are all changes, the first one from the last historical bar.
+            let previous = OHLCV {
+                open: 115.0,
+                high: 117.0,
+                low: 113.5,
+                close: 115.5,
+                volume: 1300000.0,
+            };
+            let mut bars = vec![&previous];
+            bars.extend(&sample);
+            compact_rows(bars.windows(2).map(|pair| delta(pair[0], pair[1])))
+        } else {
+            self.serialize(&sample).unwrap_or_default()
+        };
+        let heading = match count {
+            1 => pack.example_single.clone(),
+            2 => fill(&pack.example_multiple, &[("count", &count_text)]),
+            _ => fill(
+                &pack.example_partial,
+                &[("count", &count_text), ("more", &more)],
+            ),
+        };
+        format!("{}\n{}", heading, body)
+    }
+
+    /// Parses and validates a reply in this format. `previous` is the last bar sent
+    /// to the model, which `Delta` replies are relative to. JSON replies are read
+    /// leniently, see `parse_ohlcv_lenient`. Every bar must pass `bar_issues` without
+    /// errors, so an open outside [low, high] is rejected just like such a close.
+    pub fn parse_response(
+        &self,
+        content: &str,
+        previous: Option<&OHLCV>,
+        expected_count: usize,
+    ) -> Result<Vec<OHLCV>, String> {
+        let (bars, repairs) = match self {
+            Self::Json | Self::CompactJson => {
+                let (rows, repairs) = lenient_rows(content)?;
+                (rows.iter().map(from_values).collect(), repairs)
+            }
+            Self::Delta => {
+                let mut last = previous
+                    .cloned()
+                    .ok_or_else(|| "Delta replies need the last historical bar".to_string())?;
+                let (changes, repairs) = lenient_rows(content)?;
+                let mut bars = Vec::new();
+                for change in changes {
+                    let bar = OHLCV {
+                        open: last.open + change[0],
+                        high: last.high + change[1],
+                        low: last.low + change[2],
+                        close: last.close + change[3],
+                        volume: last.volume + change[4],
+                    };
+                    last = bar.clone();
+                    bars.push(bar);
+                }
+                (bars, repairs)
+            }
+            Self::Csv | Self::FixedPrecision { .. } => (parse_table(content)?, Vec::new()),
+        };
+        // A repaired reply that still fails says what was repaired, to tell model
+        // mistakes from parser guesses.
+        let with_repairs = |error: String| {
+            if repairs.is_empty() {
+                error
+            } else {
+                format!("{} (after repairs: {})", error, describe_repairs(&repairs))
+            }
+        };
+        for (i, bar) in bars.iter().enumerate() {
+            if let Some((_, message)) = bar_issues(bar)
+                .into_iter()
+                .find(|(kind, _)| kind.severity() == Severity::Error)
+            {
+                return Err(with_repairs(format!("Element {}: {}", i, message)));
+            }
+        }
+        if bars.len() != expected_count {
+            return Err(with_repairs(format!(
+                "AI returned {} OHLCV objects, but expected {}",
+                bars.len(),
+                expected_count
+            )));
+        }
+        Ok(bars)
+    }
+}
+
+/// Rough token count: words and digit runs split into sub-word pieces, one token per
+/// punctuation character and per whitespace run. Good enough to compare formats.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c.is_alphabetic() {
+            let digit = c.is_ascii_digit();
+            let mut len: usize = 1;
+            while chars.peek().is_some_and(|n| {
+                if digit {
+                    n.is_ascii_digit()
+                } else {
+                    n.is_alphabetic()
+                }
+            }) {
+                chars.next();
+                len += 1;
+            }
+            tokens += len.div_ceil(if digit { 3 } else { 4 });
+        } else if c.is_whitespace() {
+            while chars.peek().is_some_and(|n| n.is_whitespace()) {
+                chars.next();
+            }
+            tokens += 1;
+        } else {
+            tokens += 1;
+        }
+    }
+    tokens
+}
+
+/// Measures every built-in format on the same series.
+pub fn compare_formats(ohlcv_array: &[OHLCV]) -> Vec<FormatStats> {
+    let baseline = OhlcvFormat::Json
+        .serialize(ohlcv_array)
+        .map(|s| estimate_tokens(&s))
+        .unwrap_or(0)
+        .max(1);
+    OhlcvFormat::BUILT_IN
+        .iter()
+        .filter_map(|format| {
+            let text = format.serialize(ohlcv_array).ok()?;
+            let estimated_tokens = estimate_tokens(&text);
+            Some(FormatStats {
+                format: *format,
+                chars: text.chars().count(),
+                estimated_tokens,
+                savings: 1.0 - estimated_tokens as f64 / baseline as f64,
+            })
+        })
+        .collect()
+}
+
+fn values(bar: &OHLCV) -> [f64; 5] {
+    [bar.open, bar.high, bar.low, bar.close, bar.volume]
+}
+
+fn from_values(row: &[f64; 5]) -> OHLCV {
+    OHLCV {
+        open: row[0],
+        high: row[1],
+        low: row[2],
+        close: row[3],
+        volume: row[4],
+    }
+}
+
+fn delta(previous: &OHLCV, bar: &OHLCV) -> [f64; 5] {
+    let (a, b) = (values(previous), values(bar));
+    // Round away floating point noise such as 0.30000000000000004.
+    std::array::from_fn(|i| ((b[i] - a[i]) * 1e8).round() / 1e8)
+}
+
+/// Shortest round-tripping representation, without a trailing `.0`.
+fn number(value: f64) -> String {
+    format!("{}", value)
+}
+
+fn compact_rows(rows: impl Iterator<Item = [f64; 5]>) -> String {
+    let rows: Vec<String> = rows
+        .map(|row| format!("[{}]", row.map(number).join(",")))
+        .collect();
+    format!("[{}]", rows.join(","))
+}
+
+pub(crate) fn strip_code_fences(content: &str) -> String {
+    content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse_table(content: &str) -> Result<Vec<OHLCV>, String> {
+    let mut bars = Vec::new();
+    for line in strip_code_fences(content).lines() {
+        let cells: Vec<&str> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|cell| !cell.is_empty())
+            .collect();
+        // Skip header and commentary lines.
+        let Some(row) = cells
+            .iter()
+            .map(|cell| cell.parse::<f64>().ok())
+            .collect::<Option<Vec<f64>>>()
+        else {
+            continue;
+        };
+        if row.is_empty() {
+            continue;
+        }
+        let row = <[f64; 5]>::try_from(row.as_slice())
+            .map_err(|_| format!("Element {} must have exactly 5 values", bars.len()))?;
+        bars.push(from_values(&row));
+    }
+    Ok(bars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars() -> Vec<OHLCV> {
+        [
+            (100.5, 102.25, 99.75, 101.0, 1200.0),
+            (101.0, 103.5, 100.25, 103.0, 1500.5),
+            (103.0, 103.75, 101.5, 102.0, 900.0),
+        ]
+        .iter()
+        .map(|&(open, high, low, close, volume)| OHLCV {
+            open,
+            high,
+            low,
+            close,
+            volume,
+        })
+        .collect()
+    }
+
+    fn assert_same(parsed: &[OHLCV], expected: &[OHLCV]) {
+        let rows = |bars: &[OHLCV]| bars.iter().map(values).collect::<Vec<_>>();
+        assert_eq!(rows(parsed), rows(expected));
+    }
+
+    #[test]
+    fn serialized_series_parse_back_in_every_table_format() {
+        let bars = bars();
+        for format in [
+            OhlcvFormat::Json,
+            OhlcvFormat::CompactJson,
+            OhlcvFormat::Csv,
+            OhlcvFormat::FixedPrecision { decimals: 2 },
+        ] {
+            let text = format.serialize(&bars).unwrap();
+            let parsed = format.parse_response(&text, None, bars.len());
+            assert_same(
+                &parsed.unwrap_or_else(|e| panic!("{:?}: {}", format, e)),
+                &bars,
+            );
+        }
+    }
+
+    #[test]
+    fn delta_rows_parse_back_relative_to_the_previous_bar() {
+        let bars = bars();
+        let text = OhlcvFormat::Delta.serialize(&bars).unwrap();
+        let rows: Vec<[f64; 5]> = serde_json::from_str(&text).unwrap();
+        assert_eq!(rows[0], values(&bars[0]));
+        assert_eq!(rows[1], [0.5, 1.25, 0.5, 2.0, 300.5]);
+        let reply = compact_rows(rows[1..].iter().copied());
+        let parsed = OhlcvFormat::Delta
+            .parse_response(&reply, Some(&bars[0]), 2)
+            .unwrap();
+        assert_same(&parsed, &bars[1..]);
+        assert!(OhlcvFormat::Delta.parse_response(&reply, None, 2).is_err());
+    }
+
+    #[test]
+    fn fixed_precision_aligns_rounded_columns() {
+        let text = OhlcvFormat::FixedPrecision { decimals: 1 }
+            .serialize(&bars())
+            .unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], " open  high   low close volume");
+        assert_eq!(lines[1], "100.5 102.2  99.8 101.0 1200.0");
+        assert!(lines.iter().all(|line| line.len() == lines[1].len()));
+    }
+
+    #[test]
+    fn rejects_a_wrong_number_of_rows() {
+        let text = OhlcvFormat::CompactJson.serialize(&bars()).unwrap();
+        let error = OhlcvFormat::CompactJson
+            .parse_response(&text, None, 2)
+            .unwrap_err();
+        assert_eq!(error, "AI returned 3 OHLCV objects, but expected 2");
+    }
+
+    #[test]
+    fn compact_formats_use_fewer_tokens_than_json() {
+        let stats = compare_formats(&bars());
+        assert_eq!(stats.len(), OhlcvFormat::BUILT_IN.len());
+        assert_eq!(stats[0].savings, 0.0);
+        assert!(stats[1..].iter().all(|stat| stat.savings > 0.0));
+    }
+}
@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    language::{LanguagePack, fill},
+    types::OHLCV,
+};
+
+/// Descriptive statistics of an OHLCV series. All fields are zero for an empty series.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OhlcvSummary {
+    pub periods: usize,
+    pub first_close: f64,
+    pub last_close: f64,
+    pub highest_high: f64,
+    pub lowest_low: f64,
+    /// Last close minus first close.
+    pub price_change: f64,
+    pub price_change_percent: f64,
+    /// Mean close-to-close return, as a fraction.
+    pub mean_return: f64,
+    /// Standard deviation of close-to-close log returns, per period (not annualized).
+    pub realized_volatility: f64,
+    /// Largest peak-to-trough decline of close, as a positive fraction.
+    pub max_drawdown: f64,
+    pub total_volume: f64,
+    pub average_volume: f64,
+    pub median_volume: f64,
+    /// Bars closing above their open.
+    pub up_bars: usize,
+    /// Bars closing below their open.
+    pub down_bars: usize,
+    /// Least-squares slope of close against bar index, in price per period.
+    pub trend_slope: f64,
+    /// `trend_slope` relative to the mean close, in percent per period.
+    pub trend_slope_percent: f64,
+}
+
+impl From<&[OHLCV]> for OhlcvSummary {
+    fn from(ohlcv_array: &[OHLCV]) -> Self {
+        let (Some(first), Some(last)) = (ohlcv_array.first(), ohlcv_array.last()) else {
+            return Self::default();
+        };
+        let n = ohlcv_array.len() as f64;
+        let closes: Vec<f64> = ohlcv_array.iter().map(|bar| bar.close).collect();
+
+        let returns: Vec<f64> = closes
+            .windows(2)
+            .filter(|pair| pair[0] != 0.0)
+            .map(|pair| pair[1] / pair[0] - 1.0)
+            .collect();
+        let mean_return = mean(&returns);
+        let log_returns: Vec<f64> = closes
+            .windows(2)
+            .filter(|pair| pair[0] > 0.0 && pair[1] > 0.0)
+            .map(|pair| (pair[1] / pair[0]).ln())
+            .collect();
+        let log_mean = mean(&log_returns);
+        let realized_volatility = if log_returns.len() > 1 {
+            (log_returns
+                .iter()
+                .map(|r| (r - log_mean).powi(2))
+                .sum::<f64>()
+                / (log_returns.len() - 1) as f64)
+                .sqrt()
+        } else {
+            0.0
+        };
+
+        let mut peak = f64::MIN;
+        let mut max_drawdown: f64 = 0.0;
+        for close in &closes {
+            peak = peak.max(*close);
+            if peak > 0.0 {
+                max_drawdown = max_drawdown.max((peak - close) / peak);
+            }
+        }
+
+        let mut volumes: Vec<f64> = ohlcv_array.iter().map(|bar| bar.volume).collect();
+        let total_volume: f64 = volumes.iter().sum();
+        volumes.sort_by(|a, b| a.total_cmp(b));
+        let mid = volumes.len() / 2;
+        let median_volume = if volumes.len().is_multiple_of(2) {
+            (volumes[mid - 1] + volumes[mid]) / 2.0
+        } else {
+            volumes[mid]
+        };
+
+        let mean_close = mean(&closes);
+        let mean_index = (n - 1.0) / 2.0;
+        let (covariance, variance) =
+            closes
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(covariance, variance), (i, close)| {
+                    let dx = i as f64 - mean_index;
+                    (covariance + dx * (close - mean_close), variance + dx * dx)
+                });
+        let trend_slope = if variance > 0.0 {
+            covariance / variance
+        } else {
+            0.0
+        };
+
+        Self {
+            periods: ohlcv_array.len(),
+            first_close: first.close,
+            last_close: last.close,
+            highest_high: ohlcv_array
+                .iter()
+                .map(|bar| bar.high)
+                .fold(f64::MIN, f64::max),
+            lowest_low: ohlcv_array
+                .iter()
+                .map(|bar| bar.low)
+                .fold(f64::MAX, f64::min),
+            price_change: last.close - first.close,
+            price_change_percent: if first.close != 0.0 {
+                (last.close - first.close) / first.close * 100.0
+            } else {
+                0.0
+            },
+            mean_return,
+            realized_volatility,
+            max_drawdown,
+            total_volume,
+            average_volume: total_volume / n,
+            median_volume,
+            up_bars: ohlcv_array
+                .iter()
+                .filter(|bar| bar.close > bar.open)
+                .count(),
+            down_bars: ohlcv_array
+                .iter()
+                .filter(|bar| bar.close < bar.open)
+                .count(),
+            trend_slope,
+            trend_slope_percent: if mean_close != 0.0 {
+                trend_slope / mean_close * 100.0
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+impl OhlcvSummary {
+    /// Multi-line description for prompts in the language of `pack`; empty for an
+    /// empty series.
+    pub fn describe(&self, pack: &LanguagePack) -> String {
+        if self.periods == 0 {
+            return String::new();
+        }
+        let signed = |v: f64, decimals: usize| {
+            format!("{}{:.*}", if v >= 0.0 { "+" } else { "" }, decimals, v)
+        };
+        fill(
+            &pack.summary,
+            &[
+                ("periods", &self.periods.to_string()),
+                ("low", &format!("{:.2}", self.lowest_low)),
+                ("high", &format!("{:.2}", self.highest_high)),
+                ("change", &signed(self.price_change, 2)),
+                ("change_percent", &signed(self.price_change_percent, 2)),
+                ("average_volume", &format!("{:.0}", self.average_volume)),
+                ("median_volume", &format!("{:.0}", self.median_volume)),
+                ("up_bars", &self.up_bars.to_string()),
+                ("down_bars", &self.down_bars.to_string()),
+                (
+                    "volatility",
+                    &format!("{:.2}", self.realized_volatility * 100.0),
+                ),
+                ("drawdown", &format!("{:.2}", self.max_drawdown * 100.0)),
+                ("slope", &signed(self.trend_slope, 4)),
+                ("slope_percent", &signed(self.trend_slope_percent, 3)),
+            ],
+        )
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars() -> Vec<OHLCV> {
+        [
+            (100.0, 100.0, 10.0),
+            (100.0, 110.0, 40.0),
+            (110.0, 99.0, 20.0),
+            (99.0, 121.0, 90.0),
+        ]
+        .iter()
+        .map(|&(open, close, volume)| OHLCV {
+            open,
+            high: open.max(close) + 1.0,
+            low: open.min(close) - 1.0,
+            close,
+            volume,
+        })
+        .collect()
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn computes_the_statistics_of_a_fixed_series() {
+        let summary = OhlcvSummary::from(bars().as_slice());
+        assert_eq!(summary.periods, 4);
+        assert_eq!((summary.first_close, summary.last_close), (100.0, 121.0));
+        assert_eq!((summary.lowest_low, summary.highest_high), (98.0, 122.0));
+        assert_near(summary.price_change, 21.0);
+        assert_near(summary.price_change_percent, 21.0);
+        assert_near(summary.mean_return, 0.074074074074074);
+        assert_near(summary.realized_volatility, 0.155469540669819);
+        assert_near(summary.max_drawdown, 0.1);
+        assert_eq!(summary.total_volume, 160.0);
+        assert_eq!(summary.average_volume, 40.0);
+        assert_eq!(summary.median_volume, 30.0);
+        assert_eq!((summary.up_bars, summary.down_bars), (2, 1));
+        assert_near(summary.trend_slope, 5.2);
+        assert_near(summary.trend_slope_percent, 4.837209302325581);
+    }
+
+    #[test]
+    fn empty_and_single_bar_series_stay_finite() {
+        assert_eq!(OhlcvSummary::from(&[][..]), OhlcvSummary::default());
+        assert!(
+            OhlcvSummary::default()
+                .describe(&LanguagePack::english())
+                .is_empty()
+        );
+        let single = OhlcvSummary::from(&bars()[..1]);
+        assert_eq!(single.periods, 1);
+        assert_eq!(single.median_volume, 10.0);
+        assert_eq!(single.realized_volatility, 0.0);
+        assert_eq!(single.trend_slope, 0.0);
+    }
+
+    #[test]
+    fn describes_the_numbers_with_signs() {
+        let text = OhlcvSummary::from(bars().as_slice()).describe(&LanguagePack::english());
+        assert!(text.contains("Price range: 98.00 - 122.00"));
+        assert!(text.contains("Overall price change: +21.00 (+21.00%)"));
+        assert!(text.contains("Average volume: 40 (median 30)"));
+        assert!(text.contains("Up/down bars: 2/1"));
+        assert!(text.contains("Realized volatility per period: 15.55%"));
+        assert!(text.contains("Max drawdown: 10.00%"));
+        assert!(text.contains("Trend slope: +5.2000 per period (+4.837%)"));
+    }
+}
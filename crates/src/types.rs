@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::{aliyun::AliYunModelType, capability::Capability, deepseek::DeepSeekModelType};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OHLCV {
     pub open: f64,
@@ -9,6 +11,191 @@ pub struct OHLCV {
     pub volume: f64,
 }
 
+impl From<&Candle> for OHLCV {
+    fn from(candle: &Candle) -> Self {
+        Self {
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+            volume: candle.volume,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Interval {
+    #[serde(rename = "1m")]
+    M1,
+    #[serde(rename = "3m")]
+    M3,
+    #[serde(rename = "5m")]
+    M5,
+    #[serde(rename = "15m")]
+    M15,
+    #[serde(rename = "30m")]
+    M30,
+    #[serde(rename = "1h")]
+    H1,
+    #[serde(rename = "2h")]
+    H2,
+    #[serde(rename = "4h")]
+    H4,
+    #[serde(rename = "6h")]
+    H6,
+    #[serde(rename = "12h")]
+    H12,
+    #[serde(rename = "1d")]
+    D1,
+    #[serde(rename = "1w")]
+    W1,
+}
+
+impl Interval {
+    pub const ALL: [Interval; 12] = [
+        Self::M1,
+        Self::M3,
+        Self::M5,
+        Self::M15,
+        Self::M30,
+        Self::H1,
+        Self::H2,
+        Self::H4,
+        Self::H6,
+        Self::H12,
+        Self::D1,
+        Self::W1,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::M1 => "1m",
+            Self::M3 => "3m",
+            Self::M5 => "5m",
+            Self::M15 => "15m",
+            Self::M30 => "30m",
+            Self::H1 => "1h",
+            Self::H2 => "2h",
+            Self::H4 => "4h",
+            Self::H6 => "6h",
+            Self::H12 => "12h",
+            Self::D1 => "1d",
+            Self::W1 => "1w",
+        }
+    }
+
+    pub fn as_millis(&self) -> i64 {
+        const MINUTE: i64 = 60_000;
+        match self {
+            Self::M1 => MINUTE,
+            Self::M3 => 3 * MINUTE,
+            Self::M5 => 5 * MINUTE,
+            Self::M15 => 15 * MINUTE,
+            Self::M30 => 30 * MINUTE,
+            Self::H1 => 60 * MINUTE,
+            Self::H2 => 120 * MINUTE,
+            Self::H4 => 240 * MINUTE,
+            Self::H6 => 360 * MINUTE,
+            Self::H12 => 720 * MINUTE,
+            Self::D1 => 1_440 * MINUTE,
+            Self::W1 => 10_080 * MINUTE,
+        }
+    }
+
+    pub fn from_millis(millis: i64) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|interval| interval.as_millis() == millis)
+    }
+
+    /// Detects the interval of a series from the median spacing of its timestamps.
+    pub fn detect(candles: &[Candle]) -> Option<Self> {
+        detect_step_millis(candles).and_then(Self::from_millis)
+    }
+}
+
+impl std::str::FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|interval| interval.as_str() == s)
+            .ok_or_else(|| format!("unknown interval '{}'", s))
+    }
+}
+
+/// Median positive spacing between consecutive timestamps, robust to occasional gaps.
+pub fn detect_step_millis(candles: &[Candle]) -> Option<i64> {
+    let mut steps: Vec<i64> = candles
+        .windows(2)
+        .map(|pair| pair[1].timestamp - pair[0].timestamp)
+        .filter(|step| *step > 0)
+        .collect();
+    if steps.is_empty() {
+        return None;
+    }
+    steps.sort_unstable();
+    Some(steps[steps.len() / 2])
+}
+
+/// An OHLCV bar placed on the time axis. `timestamp` is the bar open time in epoch milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candle {
+    pub timestamp: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<Interval>,
+}
+
+impl Candle {
+    pub fn from_ohlcv(timestamp: i64, ohlcv: &OHLCV) -> Self {
+        Self {
+            timestamp,
+            open: ohlcv.open,
+            high: ohlcv.high,
+            low: ohlcv.low,
+            close: ohlcv.close,
+            volume: ohlcv.volume,
+            symbol: None,
+            interval: None,
+        }
+    }
+
+    pub fn to_ohlcv(&self) -> OHLCV {
+        OHLCV::from(self)
+    }
+
+    /// The step of a series: the declared interval of its last candle, else the detected spacing.
+    pub fn series_step_millis(candles: &[Candle]) -> Option<i64> {
+        candles
+            .last()
+            .and_then(|candle| candle.interval)
+            .map(|interval| interval.as_millis())
+            .or_else(|| detect_step_millis(candles))
+    }
+
+    /// Places predicted bars after `last`, one `step_millis` apart, carrying over symbol and interval.
+    pub fn continue_series(last: &Candle, predictions: &[OHLCV], step_millis: i64) -> Vec<Candle> {
+        let interval = last.interval.or_else(|| Interval::from_millis(step_millis));
+        predictions
+            .iter()
+            .enumerate()
+            .map(|(i, ohlcv)| Candle {
+                symbol: last.symbol.clone(),
+                interval,
+                ..Candle::from_ohlcv(last.timestamp + step_millis * (i as i64 + 1), ohlcv)
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: ChatRole,
@@ -24,3 +211,169 @@ pub enum ChatRole {
     #[serde(rename = "assistant")]
     Assistant,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Provider {
+    Aliyun,
+    DeepSeek,
+}
+
+impl Provider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Aliyun => "aliyun",
+            Self::DeepSeek => "deepseek",
+        }
+    }
+}
+
+/// A model identifier qualified by its provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModelId {
+    Aliyun(AliYunModelType),
+    DeepSeek(DeepSeekModelType),
+}
+
+impl ModelId {
+    pub fn provider(&self) -> Provider {
+        match self {
+            Self::Aliyun(_) => Provider::Aliyun,
+            Self::DeepSeek(_) => Provider::DeepSeek,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Aliyun(model_type) => model_type.as_str(),
+            Self::DeepSeek(model_type) => model_type.as_str(),
+        }
+    }
+}
+
+/// Provider-independent classification of client errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorKind {
+    Api,
+    Network,
+    Parse,
+    Config,
+    Timeout,
+    ModelNotSupported,
+    StreamingNotSupported,
+    RateLimit,
+    Auth,
+    ContextLength,
+}
+
+/// Output constraint sent as `response_format` to OpenAI-compatible endpoints.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ResponseFormat {
+    Text,
+    /// Any valid JSON object.
+    JsonObject,
+    /// JSON matching `schema`, for models with `Capability::JsonSchema`.
+    JsonSchema {
+        name: String,
+        schema: serde_json::Value,
+        strict: bool,
+    },
+}
+
+impl ResponseFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::JsonObject => "json_object",
+            Self::JsonSchema { .. } => "json_schema",
+        }
+    }
+
+    /// Capability a model needs to honour this format.
+    pub fn required_capability(&self) -> Option<Capability> {
+        match self {
+            Self::Text => None,
+            Self::JsonObject => Some(Capability::JsonMode),
+            Self::JsonSchema { .. } => Some(Capability::JsonSchema),
+        }
+    }
+
+    pub fn to_request_value(&self) -> serde_json::Value {
+        match self {
+            Self::JsonSchema {
+                name,
+                schema,
+                strict,
+            } => serde_json::json!({
+                "type": "json_schema",
+                "json_schema": {"name": name, "schema": schema, "strict": strict},
+            }),
+            other => serde_json::json!({"type": other.as_str()}),
+        }
+    }
+}
+
+/// Tokens billed for one or more requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl TokenUsage {
+    /// Reads `usage` from an OpenAI-compatible or DashScope response; zero when absent.
+    pub fn from_response(response: &serde_json::Value) -> Self {
+        let usage = &response["usage"];
+        let count = |keys: [&str; 2]| {
+            keys.iter()
+                .find_map(|key| usage[*key].as_u64())
+                .unwrap_or(0) as u32
+        };
+        let prompt_tokens = count(["prompt_tokens", "input_tokens"]);
+        let completion_tokens = count(["completion_tokens", "output_tokens"]);
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: usage["total_tokens"]
+                .as_u64()
+                .map(|total| total as u32)
+                .unwrap_or(prompt_tokens + completion_tokens),
+        }
+    }
+
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// Result of `predict_ohlcv_with_report`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PredictionReport {
+    pub predictions: Vec<OHLCV>,
+    /// Requests sent, including the first one.
+    pub attempts: u32,
+    /// Tokens used by all attempts.
+    pub usage: TokenUsage,
+    /// Validation errors of the rejected replies, in order.
+    pub rejected: Vec<String>,
+}
+
+/// Error of `predict_ohlcv_with_report`, with the report of the attempts made before
+/// it, including their token usage and, for a final invalid reply, its validation error.
+#[derive(Debug, thiserror::Error)]
+#[error("{error}")]
+pub struct PredictionFailure<E> {
+    pub error: E,
+    pub report: PredictionReport,
+}
+
+impl<E> From<E> for PredictionFailure<E> {
+    fn from(error: E) -> Self {
+        Self {
+            error,
+            report: PredictionReport::default(),
+        }
+    }
+}
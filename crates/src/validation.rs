@@ -0,0 +1,489 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::types::{Candle, OHLCV};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IssueKind {
+    NonFinite,
+    HighBelowLow,
+    OpenOutOfRange,
+    CloseOutOfRange,
+    NegativeVolume,
+    ZeroRange,
+    DuplicateTimestamp,
+    OutOfOrderTimestamp,
+    ExtremeJump,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    /// The bar is technically impossible and must not be used as is.
+    Error,
+    /// The bar is plausible but suspicious.
+    Warning,
+}
+
+impl IssueKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::ZeroRange | Self::ExtremeJump => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    pub index: usize,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepairPolicy {
+    /// Report issues without modifying the series.
+    None,
+    /// Force fields back into a consistent bar: widen high/low, zero negative volume,
+    /// replace non-finite values with the previous close, sort and de-duplicate timestamps.
+    Clamp,
+    /// Remove bars with error-level issues and duplicate timestamps; out-of-order bars
+    /// are sorted into place.
+    Drop,
+    /// Replace bars with error-level issues by linear interpolation between valid neighbours.
+    Interpolate,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationOptions {
+    /// Relative close-to-close change above which an `ExtremeJump` warning is raised.
+    pub max_jump: f64,
+    pub repair: RepairPolicy,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        Self {
+            max_jump: 0.5,
+            repair: RepairPolicy::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QualityReport {
+    pub total: usize,
+    pub issues: Vec<Issue>,
+    /// Bars modified in place by the repair policy.
+    pub repaired: usize,
+    /// Bars removed by the repair policy.
+    pub dropped: usize,
+}
+
+impl QualityReport {
+    /// True when no error-level issue was found.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.kind.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.kind.severity() == Severity::Warning)
+    }
+
+    pub fn counts(&self) -> HashMap<IssueKind, usize> {
+        let mut counts = HashMap::new();
+        for issue in &self.issues {
+            *counts.entry(issue.kind).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Checks a single bar in isolation, most severe inconsistencies first. Also used to
+/// reject predicted bars, so an open outside [low, high] fails a reply too.
+pub fn bar_issues(bar: &OHLCV) -> Vec<(IssueKind, String)> {
+    let mut issues = Vec::new();
+    let fields = [bar.open, bar.high, bar.low, bar.close, bar.volume];
+    if fields.iter().any(|v| !v.is_finite()) {
+        issues.push((
+            IssueKind::NonFinite,
+            "values must be finite numbers".to_string(),
+        ));
+        return issues;
+    }
+    if bar.high < bar.low {
+        issues.push((
+            IssueKind::HighBelowLow,
+            "high cannot be lower than low".to_string(),
+        ));
+    }
+    if bar.close < bar.low || bar.close > bar.high {
+        issues.push((
+            IssueKind::CloseOutOfRange,
+            "close must be between low and high".to_string(),
+        ));
+    }
+    if bar.open < bar.low || bar.open > bar.high {
+        issues.push((
+            IssueKind::OpenOutOfRange,
+            "open must be between low and high".to_string(),
+        ));
+    }
+    if bar.volume < 0.0 {
+        issues.push((
+            IssueKind::NegativeVolume,
+            "volume must be non-negative".to_string(),
+        ));
+    }
+    if bar.high == bar.low {
+        issues.push((
+            IssueKind::ZeroRange,
+            "high equals low (zero-range bar)".to_string(),
+        ));
+    }
+    issues
+}
+
+fn series_issues(bars: &[OHLCV], options: &ValidationOptions) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for (index, bar) in bars.iter().enumerate() {
+        for (kind, message) in bar_issues(bar) {
+            issues.push(Issue {
+                index,
+                kind,
+                message,
+            });
+        }
+        if index > 0 {
+            let previous = bars[index - 1].close;
+            if previous.is_finite() && previous != 0.0 && bar.close.is_finite() {
+                let change = (bar.close - previous) / previous;
+                if change.abs() > options.max_jump {
+                    issues.push(Issue {
+                        index,
+                        kind: IssueKind::ExtremeJump,
+                        message: format!("close changed by {:.1}%", change * 100.0),
+                    });
+                }
+            }
+        }
+    }
+    issues
+}
+
+fn timestamp_issues(candles: &[Candle]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for (index, pair) in candles.windows(2).enumerate() {
+        let (previous, current) = (pair[0].timestamp, pair[1].timestamp);
+        if current == previous {
+            issues.push(Issue {
+                index: index + 1,
+                kind: IssueKind::DuplicateTimestamp,
+                message: format!("duplicate timestamp {}", current),
+            });
+        } else if current < previous {
+            issues.push(Issue {
+                index: index + 1,
+                kind: IssueKind::OutOfOrderTimestamp,
+                message: format!("timestamp {} precedes {}", current, previous),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_ohlcv(ohlcv_array: &[OHLCV], options: &ValidationOptions) -> QualityReport {
+    QualityReport {
+        total: ohlcv_array.len(),
+        issues: series_issues(ohlcv_array, options),
+        ..Default::default()
+    }
+}
+
+pub fn validate_candles(candles: &[Candle], options: &ValidationOptions) -> QualityReport {
+    let bars: Vec<OHLCV> = candles.iter().map(OHLCV::from).collect();
+    let mut issues = series_issues(&bars, options);
+    issues.extend(timestamp_issues(candles));
+    issues.sort_by_key(|issue| issue.index);
+    QualityReport {
+        total: candles.len(),
+        issues,
+        ..Default::default()
+    }
+}
+
+/// Validates and applies `options.repair`; the report describes the input series.
+pub fn repair_ohlcv(
+    ohlcv_array: &[OHLCV],
+    options: &ValidationOptions,
+) -> (Vec<OHLCV>, QualityReport) {
+    let mut report = validate_ohlcv(ohlcv_array, options);
+    let invalid = invalid_indices(&report, ohlcv_array.len());
+    let (bars, repaired, dropped) = repair_bars(ohlcv_array.to_vec(), &invalid, options.repair);
+    report.repaired = repaired;
+    report.dropped = dropped;
+    (bars, report)
+}
+
+/// Validates and applies `options.repair`, also resolving duplicate and out-of-order timestamps.
+pub fn repair_candles(
+    candles: &[Candle],
+    options: &ValidationOptions,
+) -> (Vec<Candle>, QualityReport) {
+    let mut report = validate_candles(candles, options);
+    if options.repair == RepairPolicy::None {
+        return (candles.to_vec(), report);
+    }
+    // Sorting first keeps one early bar with a far-off timestamp from shadowing the rest.
+    let mut candles = candles.to_vec();
+    candles.sort_by_key(|candle| candle.timestamp);
+    candles.dedup_by_key(|candle| candle.timestamp);
+    let dropped = report.total - candles.len();
+    let bars: Vec<OHLCV> = candles.iter().map(OHLCV::from).collect();
+    let invalid = invalid_indices(&validate_ohlcv(&bars, options), bars.len());
+    let keep: Vec<bool> = invalid.iter().map(|bad| !bad).collect();
+    let (bars, repaired, bar_dropped) = repair_bars(bars, &invalid, options.repair);
+    let candles = if options.repair == RepairPolicy::Drop {
+        candles
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(candle, keep)| keep.then_some(candle))
+            .collect()
+    } else {
+        candles
+            .into_iter()
+            .zip(bars)
+            .map(|(candle, bar)| Candle {
+                open: bar.open,
+                high: bar.high,
+                low: bar.low,
+                close: bar.close,
+                volume: bar.volume,
+                ..candle
+            })
+            .collect()
+    };
+    report.repaired = repaired;
+    report.dropped = dropped + bar_dropped;
+    (candles, report)
+}
+
+fn invalid_indices(report: &QualityReport, len: usize) -> Vec<bool> {
+    let mut invalid = vec![false; len];
+    for issue in report.errors() {
+        invalid[issue.index] = true;
+    }
+    invalid
+}
+
+fn repair_bars(
+    mut bars: Vec<OHLCV>,
+    invalid: &[bool],
+    policy: RepairPolicy,
+) -> (Vec<OHLCV>, usize, usize) {
+    let count = invalid.iter().filter(|bad| **bad).count();
+    match policy {
+        RepairPolicy::None => (bars, 0, 0),
+        RepairPolicy::Drop => {
+            let kept = bars
+                .into_iter()
+                .zip(invalid)
+                .filter_map(|(bar, bad)| (!bad).then_some(bar))
+                .collect();
+            (kept, 0, count)
+        }
+        RepairPolicy::Clamp => {
+            // Leading bars have no previous close, so borrow the first finite one.
+            let mut fallback = bars
+                .iter()
+                .map(|bar| bar.close)
+                .find(|close| close.is_finite())
+                .unwrap_or(0.0);
+            for i in 0..bars.len() {
+                if invalid[i] {
+                    bars[i] = clamp_bar(&bars[i], fallback);
+                }
+                fallback = bars[i].close;
+            }
+            (bars, count, 0)
+        }
+        RepairPolicy::Interpolate => {
+            let valid: Vec<usize> = (0..bars.len()).filter(|i| !invalid[*i]).collect();
+            if valid.is_empty() {
+                return (Vec::new(), 0, bars.len());
+            }
+            for i in 0..bars.len() {
+                if !invalid[i] {
+                    continue;
+                }
+                let previous = valid.iter().rev().find(|v| **v < i).copied();
+                let next = valid.iter().find(|v| **v > i).copied();
+                bars[i] = match (previous, next) {
+                    (Some(p), Some(n)) => {
+                        let t = (i - p) as f64 / (n - p) as f64;
+                        let lerp = |a: f64, b: f64| a + (b - a) * t;
+                        OHLCV {
+                            open: lerp(bars[p].open, bars[n].open),
+                            high: lerp(bars[p].high, bars[n].high),
+                            low: lerp(bars[p].low, bars[n].low),
+                            close: lerp(bars[p].close, bars[n].close),
+                            volume: lerp(bars[p].volume, bars[n].volume),
+                        }
+                    }
+                    (Some(nearest), None) | (None, Some(nearest)) => bars[nearest].clone(),
+                    (None, None) => unreachable!("at least one valid bar exists"),
+                };
+                bars[i] = clamp_bar(&bars[i], bars[i].close);
+            }
+            (bars, count, 0)
+        }
+    }
+}
+
+fn clamp_bar(bar: &OHLCV, fallback: f64) -> OHLCV {
+    let finite = |v: f64| if v.is_finite() { v } else { fallback };
+    let (open, close) = (finite(bar.open), finite(bar.close));
+    OHLCV {
+        open,
+        high: finite(bar.high).max(open).max(close),
+        low: finite(bar.low).min(open).min(close),
+        close,
+        volume: if bar.volume.is_finite() {
+            bar.volume.max(0.0)
+        } else {
+            0.0
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::OhlcvFormat;
+
+    fn bar(open: f64, high: f64, low: f64, close: f64) -> OHLCV {
+        OHLCV {
+            open,
+            high,
+            low,
+            close,
+            volume: 100.0,
+        }
+    }
+
+    fn candles(timestamps: &[i64]) -> Vec<Candle> {
+        timestamps
+            .iter()
+            .enumerate()
+            .map(|(i, &timestamp)| {
+                let close = 10.0 + i as f64;
+                Candle::from_ohlcv(
+                    timestamp,
+                    &bar(close - 0.5, close + 1.0, close - 1.0, close),
+                )
+            })
+            .collect()
+    }
+
+    fn timestamps(candles: &[Candle]) -> Vec<i64> {
+        candles.iter().map(|candle| candle.timestamp).collect()
+    }
+
+    fn options(repair: RepairPolicy) -> ValidationOptions {
+        ValidationOptions {
+            repair,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn open_outside_the_range_is_an_error() {
+        let kinds: Vec<IssueKind> = bar_issues(&bar(12.0, 11.0, 9.0, 10.0))
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect();
+        assert_eq!(kinds, vec![IssueKind::OpenOutOfRange]);
+        assert_eq!(IssueKind::OpenOutOfRange.severity(), Severity::Error);
+    }
+
+    #[test]
+    fn predictions_with_the_open_outside_the_range_are_rejected() {
+        let reply = r#"[{"open": 12, "high": 11, "low": 9, "close": 10, "volume": 100}]"#;
+        let error = OhlcvFormat::Json
+            .parse_response(reply, None, 1)
+            .unwrap_err();
+        assert!(error.contains("open must be between low and high"));
+    }
+
+    #[test]
+    fn reports_timestamp_and_jump_issues_in_index_order() {
+        let mut series = candles(&[1, 2, 2, 1]);
+        series[1].close = 30.0;
+        series[1].high = 31.0;
+        let report = validate_candles(&series, &ValidationOptions::default());
+        let found: Vec<(usize, IssueKind)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.index, issue.kind))
+            .collect();
+        assert!(found.contains(&(1, IssueKind::ExtremeJump)));
+        assert!(found.contains(&(2, IssueKind::DuplicateTimestamp)));
+        assert!(found.contains(&(3, IssueKind::OutOfOrderTimestamp)));
+        assert!(found.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert!(!report.is_valid());
+        assert_eq!(report.warnings().count(), 2);
+    }
+
+    #[test]
+    fn drop_survives_an_early_far_future_timestamp() {
+        let (repaired, report) =
+            repair_candles(&candles(&[9_999, 2, 3, 4, 5]), &options(RepairPolicy::Drop));
+        assert_eq!(timestamps(&repaired), vec![2, 3, 4, 5, 9_999]);
+        assert_eq!(report.dropped, 0);
+    }
+
+    #[test]
+    fn drop_removes_duplicates_and_invalid_bars() {
+        let mut series = candles(&[1, 2, 2, 3, 4]);
+        series[3].high = series[3].low - 1.0;
+        let (repaired, report) = repair_candles(&series, &options(RepairPolicy::Drop));
+        assert_eq!(timestamps(&repaired), vec![1, 2, 4]);
+        assert_eq!(repaired[1].close, series[1].close);
+        assert_eq!(report.dropped, 2);
+    }
+
+    #[test]
+    fn clamp_widens_the_range_and_zeroes_negative_volume() {
+        let mut broken = bar(12.0, 11.0, 9.0, 10.0);
+        broken.volume = -5.0;
+        let (repaired, report) = repair_ohlcv(
+            &[bar(10.0, 11.0, 9.0, 10.0), broken],
+            &options(RepairPolicy::Clamp),
+        );
+        assert_eq!(report.repaired, 1);
+        assert_eq!(repaired[1].high, 12.0);
+        assert_eq!(repaired[1].volume, 0.0);
+        assert!(validate_ohlcv(&repaired, &ValidationOptions::default()).is_valid());
+    }
+
+    #[test]
+    fn interpolate_replaces_invalid_bars_between_valid_neighbours() {
+        let series = [
+            bar(10.0, 11.0, 9.0, 10.0),
+            bar(f64::NAN, 11.0, 9.0, 10.0),
+            bar(12.0, 13.0, 11.0, 12.0),
+        ];
+        let (repaired, report) = repair_ohlcv(&series, &options(RepairPolicy::Interpolate));
+        assert_eq!(report.repaired, 1);
+        assert_eq!(repaired[1].open, 11.0);
+        assert_eq!(repaired[1].close, 11.0);
+    }
+}